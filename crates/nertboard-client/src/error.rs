@@ -0,0 +1,236 @@
+use nertboard_core::{ErrorCode, ErrorResponse};
+use reqwest::{header, Response, StatusCode};
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("unauthorized request, api key is missing or invalid")]
+    Unauthorized,
+    #[error("unauthorized request, not enough rights")]
+    Forbidden,
+    #[error("player key is invalid")]
+    InvalidPlayer,
+    #[error("{0}")]
+    InvalidPlayerName(String),
+    #[error("{0}")]
+    RejectedText(String),
+    #[error("invalid board name: {0}")]
+    InvalidBoardName(String),
+    #[error("a board called {0} already exists")]
+    BoardAlreadyExists(String),
+    #[error("a board called {0} not found")]
+    NoSuchBoard(String),
+    #[error("the player has no scores on the board")]
+    NoPlayerScore,
+    #[error("key not found")]
+    NoSuchKey,
+    #[error("score not found")]
+    NoSuchScore,
+    #[error("player not found")]
+    NoSuchPlayer,
+    #[error("player is banned from the board")]
+    PlayerBanned,
+    #[error("{0}")]
+    ScoreRejected(String),
+    #[error("{0}")]
+    InvalidSettings(String),
+    #[error("{0}")]
+    InvalidSignature(String),
+    #[error("cannot remove the only admin key of the board")]
+    LastAdminKey,
+    /// Retry after the given number of seconds, if the server told when.
+    #[error("too many requests")]
+    TooManyRequests { retry_after: Option<u64> },
+    #[error("{0}")]
+    QuotaExceeded(String),
+    #[error("invalid request: {message}")]
+    InvalidRequest {
+        message: String,
+        details: Option<String>,
+    },
+    #[error("server error ({status}): {message}")]
+    Server { status: StatusCode, message: String },
+    #[error("request failed: {0}")]
+    Request(#[from] reqwest::Error),
+}
+
+impl Error {
+    /// Decodes the error from the status code and the body of the response.
+    fn decode(board_name: &str, status: StatusCode, retry_after: Option<u64>, body: &str) -> Self {
+        let response: Option<ErrorResponse> = serde_json::from_str(body).ok();
+        let code = match &response {
+            Some(response) => response.code,
+            // Not a structured error, so it might not come from the server at all
+            // (e.g. a wrong url or a proxy), only trust statuses with a single meaning
+            None => match status {
+                StatusCode::UNAUTHORIZED => ErrorCode::Unauthorized,
+                StatusCode::FORBIDDEN => ErrorCode::Forbidden,
+                StatusCode::TOO_MANY_REQUESTS => ErrorCode::TooManyRequests,
+                _ => ErrorCode::Unknown,
+            },
+        };
+
+        let board_name = board_name.to_owned();
+        let (message, details) = match response {
+            Some(response) => (response.message, response.details),
+            None => (body.to_owned(), None),
+        };
+        match code {
+            ErrorCode::Unauthorized => Self::Unauthorized,
+            ErrorCode::Forbidden => Self::Forbidden,
+            ErrorCode::InvalidPlayer => Self::InvalidPlayer,
+            ErrorCode::InvalidPlayerName => Self::InvalidPlayerName(message),
+            ErrorCode::RejectedText => Self::RejectedText(message),
+            ErrorCode::InvalidBoardName => Self::InvalidBoardName(board_name),
+            ErrorCode::BoardAlreadyExists => Self::BoardAlreadyExists(board_name),
+            ErrorCode::NoSuchBoard => Self::NoSuchBoard(board_name),
+            ErrorCode::NoPlayerScore => Self::NoPlayerScore,
+            ErrorCode::NoSuchKey => Self::NoSuchKey,
+            ErrorCode::NoSuchScore => Self::NoSuchScore,
+            ErrorCode::NoSuchPlayer => Self::NoSuchPlayer,
+            ErrorCode::PlayerBanned => Self::PlayerBanned,
+            ErrorCode::ScoreRejected => Self::ScoreRejected(message),
+            ErrorCode::InvalidSettings => Self::InvalidSettings(message),
+            ErrorCode::InvalidSignature => Self::InvalidSignature(message),
+            ErrorCode::LastAdminKey => Self::LastAdminKey,
+            ErrorCode::TooManyRequests => Self::TooManyRequests { retry_after },
+            ErrorCode::QuotaExceeded => Self::QuotaExceeded(message),
+            ErrorCode::InvalidRequest => Self::InvalidRequest { message, details },
+            ErrorCode::NotFound
+            | ErrorCode::MethodNotAllowed
+            | ErrorCode::Internal
+            | ErrorCode::Unknown => Self::Server { status, message },
+        }
+    }
+}
+
+/// Turns an unsuccessful response into an [`Error`].
+pub(crate) async fn check_response(board_name: &str, response: Response) -> Result<Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let retry_after = response
+        .headers()
+        .get(header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok());
+    let body = response.text().await?;
+    Err(Error::decode(board_name, status, retry_after, &body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Errors cannot be compared because of [`reqwest::Error`], compare their debug output.
+    fn assert_decoded(error: Error, expected: Error) {
+        assert_eq!(format!("{:?}", error), format!("{:?}", expected));
+    }
+
+    #[test]
+    fn test_decode_structured() {
+        let board = || "test-table".to_owned();
+        let message = || "message".to_owned();
+        let status = StatusCode::BAD_REQUEST;
+        let cases = [
+            (ErrorCode::Unauthorized, Error::Unauthorized),
+            (ErrorCode::Forbidden, Error::Forbidden),
+            (ErrorCode::InvalidPlayer, Error::InvalidPlayer),
+            (
+                ErrorCode::InvalidPlayerName,
+                Error::InvalidPlayerName(message()),
+            ),
+            (ErrorCode::RejectedText, Error::RejectedText(message())),
+            (
+                ErrorCode::InvalidBoardName,
+                Error::InvalidBoardName(board()),
+            ),
+            (
+                ErrorCode::BoardAlreadyExists,
+                Error::BoardAlreadyExists(board()),
+            ),
+            (ErrorCode::NoSuchBoard, Error::NoSuchBoard(board())),
+            (ErrorCode::NoPlayerScore, Error::NoPlayerScore),
+            (ErrorCode::NoSuchKey, Error::NoSuchKey),
+            (ErrorCode::NoSuchScore, Error::NoSuchScore),
+            (ErrorCode::NoSuchPlayer, Error::NoSuchPlayer),
+            (ErrorCode::PlayerBanned, Error::PlayerBanned),
+            (ErrorCode::ScoreRejected, Error::ScoreRejected(message())),
+            (
+                ErrorCode::InvalidSettings,
+                Error::InvalidSettings(message()),
+            ),
+            (
+                ErrorCode::InvalidSignature,
+                Error::InvalidSignature(message()),
+            ),
+            (ErrorCode::LastAdminKey, Error::LastAdminKey),
+            (
+                ErrorCode::TooManyRequests,
+                Error::TooManyRequests {
+                    retry_after: Some(5),
+                },
+            ),
+            (ErrorCode::QuotaExceeded, Error::QuotaExceeded(message())),
+            (
+                ErrorCode::InvalidRequest,
+                Error::InvalidRequest {
+                    message: message(),
+                    details: Some("details".to_owned()),
+                },
+            ),
+            (
+                ErrorCode::NotFound,
+                Error::Server {
+                    status,
+                    message: message(),
+                },
+            ),
+            (
+                ErrorCode::Internal,
+                Error::Server {
+                    status,
+                    message: message(),
+                },
+            ),
+        ];
+        for (code, expected) in cases {
+            let response = ErrorResponse {
+                code,
+                message: message(),
+                details: Some("details".to_owned()),
+            };
+            let body = serde_json::to_string(&response).unwrap();
+            assert_decoded(Error::decode(&board(), status, Some(5), &body), expected);
+        }
+    }
+
+    #[test]
+    fn test_decode_unstructured() {
+        // A plain 404 may come from a wrong url rather than a missing board
+        let error = Error::decode("test-table", StatusCode::NOT_FOUND, None, "Not Found");
+        let expected = Error::Server {
+            status: StatusCode::NOT_FOUND,
+            message: "Not Found".to_owned(),
+        };
+        assert_decoded(error, expected);
+
+        let error = Error::decode("test-table", StatusCode::UNAUTHORIZED, None, "");
+        assert_decoded(error, Error::Unauthorized);
+    }
+
+    #[test]
+    fn test_decode_retry_after() {
+        let error = Error::decode("test-table", StatusCode::TOO_MANY_REQUESTS, Some(30), "");
+        let expected = Error::TooManyRequests {
+            retry_after: Some(30),
+        };
+        assert_decoded(error, expected);
+
+        let error = Error::decode("test-table", StatusCode::TOO_MANY_REQUESTS, None, "");
+        assert_decoded(error, Error::TooManyRequests { retry_after: None });
+    }
+}
@@ -1,53 +1,396 @@
-pub use nertboard_core::{Player, ScoreEntry};
-use reqwest::{Client, Result, Url};
+mod error;
 
+pub use self::error::{Error, Result};
+pub use nertboard_core::{
+    BanInfo, BoardCreate, BoardInfo, BoardInfoUpdate, BoardKeys, BoardQuery, BoardSettings,
+    BoardSettingsUpdate, KeyAuthority, KeyCreate, KeyInfo, NameChange, NewKey, Player, PlayerRank,
+    RecordQuery, ScoreEntry, ScoreOrder, ScorePolicy, ScoreQuery, ScoreRecord, ScoreUpdate,
+    ScoreValidation, ScoresPage, SigningSecret, SubmissionLimit, SubmitResult, TimePeriod,
+};
+
+use self::error::check_response;
+
+use nertboard_core::signing;
+
+use reqwest::{Client, RequestBuilder, Response, Url};
+
+/// Client for a single board on a nertboard server.
 pub struct Nertboard {
+    /// Base url of the server.
     url: Url,
+    board_name: String,
     api_key: Option<String>,
+    /// Secret used to sign submissions, if the board requires it.
+    signing_secret: Option<String>,
+    /// Server-level key required to create boards.
+    operator_key: Option<String>,
     client: Client,
 }
 
 impl Nertboard {
-    pub fn new(url: impl reqwest::IntoUrl, api_key: Option<String>) -> Result<Self> {
+    pub fn new(
+        url: impl reqwest::IntoUrl,
+        board_name: impl Into<String>,
+        api_key: Option<String>,
+    ) -> Result<Self> {
         Ok(Self {
             url: url.into_url()?,
+            board_name: board_name.into(),
             api_key,
+            signing_secret: None,
+            operator_key: None,
             client: Client::new(),
         })
     }
 
-    pub async fn create_player(&self, name: &str) -> Result<Player> {
+    pub fn board_name(&self) -> &str {
+        &self.board_name
+    }
+
+    /// Set the api key used for board requests,
+    /// e.g. after receiving the keys from [`Nertboard::create_board`].
+    pub fn set_api_key(&mut self, api_key: Option<String>) {
+        self.api_key = api_key;
+    }
+
+    /// Set the secret used to sign score submissions,
+    /// e.g. after receiving it from [`Nertboard::enable_signing`].
+    pub fn set_signing_secret(&mut self, secret: Option<String>) {
+        self.signing_secret = secret;
+    }
+
+    /// Set the operator key used to create boards,
+    /// not needed if the server allows anyone to create boards.
+    pub fn set_operator_key(&mut self, operator_key: Option<String>) {
+        self.operator_key = operator_key;
+    }
+
+    /// Constructs the url to the endpoint relative to the base url.
+    fn endpoint(&self, segments: &[&str]) -> Url {
         let mut url = self.url.clone();
-        url.set_path("player/create");
-        let req = self.client.post(url).json(&name);
+        url.path_segments_mut()
+            .expect("server url cannot be a base")
+            .pop_if_empty()
+            .extend(segments);
+        url
+    }
+
+    fn board_url(&self) -> Url {
+        self.endpoint(&["board", &self.board_name])
+    }
+
+    async fn send(&self, req: RequestBuilder) -> Result<Response> {
         let response = req.send().await?;
-        response.json().await
+        check_response(&self.board_name, response).await
     }
 
-    pub async fn fetch_scores(&self) -> Result<Vec<ScoreEntry>> {
-        let mut req = self.client.get(self.url.clone());
+    fn sign(
+        &self,
+        req: RequestBuilder,
+        secret: &str,
+        player_id: i32,
+        entry: &ScoreEntry,
+    ) -> RequestBuilder {
+        use rand::{distributions::Alphanumeric, Rng};
+
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |time| time.as_secs() as i64);
+        let nonce: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(32)
+            .map(char::from)
+            .collect();
+        let message =
+            signing::submission_message(&self.board_name, player_id, entry, timestamp, &nonce);
+        req.header(signing::SIGNATURE_HEADER, signing::sign(secret, &message))
+            .header(signing::TIMESTAMP_HEADER, timestamp)
+            .header(signing::NONCE_HEADER, nonce)
+    }
+
+    fn with_api_key(&self, mut req: RequestBuilder) -> RequestBuilder {
         if let Some(key) = &self.api_key {
             req = req.header("api-key", key);
         }
+        req
+    }
 
-        let response = req.send().await?;
-        response.json().await
+    pub async fn create_player(&self, name: &str) -> Result<Player> {
+        let url = self.endpoint(&["player", "create"]);
+        let req = self.client.post(url).json(&name);
+        let response = self.send(req).await?;
+        Ok(response.json().await?)
+    }
+
+    /// Replace the key of the player with a new one.
+    /// The old key stops working immediately.
+    pub async fn regenerate_player_key(&self, player: &Player) -> Result<Player> {
+        let url = self.endpoint(&["player", &player.id.to_string(), "regenerate"]);
+        let req = self.client.post(url).header("player-key", &player.key);
+        let response = self.send(req).await?;
+        Ok(response.json().await?)
+    }
+
+    /// Change the name of the player.
+    /// Returns the name as stored by the server.
+    pub async fn rename_player(&self, player: &Player, name: &str) -> Result<String> {
+        let url = self.endpoint(&["player", &player.id.to_string(), "rename"]);
+        let req = self
+            .client
+            .post(url)
+            .header("player-key", &player.key)
+            .json(&name);
+        let response = self.send(req).await?;
+        Ok(response.json().await?)
+    }
+
+    /// Fetch the past names of the player, oldest first.
+    pub async fn fetch_name_history(&self, player: &Player) -> Result<Vec<NameChange>> {
+        let url = self.endpoint(&["player", &player.id.to_string(), "names"]);
+        let req = self.client.get(url).header("player-key", &player.key);
+        let response = self.send(req).await?;
+        Ok(response.json().await?)
+    }
+
+    /// Recover the player with a lost key using the recovery code.
+    /// The returned player has a new key and a new recovery code.
+    pub async fn recover_player(&self, player_id: i32, recovery_code: &str) -> Result<Player> {
+        let url = self.endpoint(&["player", &player_id.to_string(), "recover"]);
+        let req = self.client.post(url).json(&recovery_code);
+        let response = self.send(req).await?;
+        Ok(response.json().await?)
     }
 
-    pub async fn submit_score(&self, player: &Player, entry: &ScoreEntry) -> Result<()> {
+    /// Create the board on the server.
+    /// The returned keys are only shown once, so make sure to save them.
+    /// Requires the operator key, see [`Nertboard::set_operator_key`].
+    pub async fn create_board(&self, settings: &BoardSettings) -> Result<BoardKeys> {
+        let url = self.endpoint(&["board", "create"]);
+        let board = BoardCreate {
+            name: self.board_name.clone(),
+            settings: settings.clone(),
+        };
+        let mut req = self.client.post(url).json(&board);
+        if let Some(key) = &self.operator_key {
+            req = req.header("operator-key", key);
+        }
+        let response = self.send(req).await?;
+        Ok(response.json().await?)
+    }
+
+    /// List all boards on the server.
+    /// Requires the operator key, unless the server allows anyone to create boards.
+    pub async fn list_boards(&self, query: &BoardQuery) -> Result<Vec<BoardInfo>> {
+        let url = self.endpoint(&["boards"]);
+        let mut req = self.client.get(url).query(query);
+        if let Some(key) = &self.operator_key {
+            req = req.header("operator-key", key);
+        }
+        let response = self.send(req).await?;
+        Ok(response.json().await?)
+    }
+
+    /// Fetch the information about the board and its settings.
+    /// Requires the read key, unless the board is public.
+    pub async fn fetch_board_info(&self) -> Result<BoardInfo> {
+        let url = self.endpoint(&["board", &self.board_name, "info"]);
+        let req = self.with_api_key(self.client.get(url));
+        let response = self.send(req).await?;
+        Ok(response.json().await?)
+    }
+
+    /// Change the display name or the description of the board,
+    /// returns the updated information.
+    /// Requires the admin key.
+    pub async fn update_board_info(&self, update: &BoardInfoUpdate) -> Result<BoardInfo> {
+        let url = self.endpoint(&["board", &self.board_name, "info"]);
+        let req = self.with_api_key(self.client.patch(url)).json(update);
+        let response = self.send(req).await?;
+        Ok(response.json().await?)
+    }
+
+    /// Delete the board together with all its scores.
+    /// Requires the admin key.
+    pub async fn delete_board(&self) -> Result<()> {
+        let req = self.with_api_key(self.client.delete(self.board_url()));
+        self.send(req).await?;
+        Ok(())
+    }
+
+    /// Change the settings of the board and return the new settings.
+    /// Requires the admin key.
+    pub async fn update_settings(&self, update: &BoardSettingsUpdate) -> Result<BoardSettings> {
+        let url = self.endpoint(&["board", &self.board_name, "settings"]);
+        let req = self.with_api_key(self.client.patch(url)).json(update);
+        let response = self.send(req).await?;
+        Ok(response.json().await?)
+    }
+
+    /// List all keys of the board.
+    /// Requires the admin key.
+    pub async fn list_keys(&self) -> Result<Vec<KeyInfo>> {
+        let url = self.endpoint(&["board", &self.board_name, "keys"]);
+        let req = self.with_api_key(self.client.get(url));
+        let response = self.send(req).await?;
+        Ok(response.json().await?)
+    }
+
+    /// Mint a new key for the board.
+    /// Requires the admin key.
+    pub async fn create_key(&self, key: &KeyCreate) -> Result<NewKey> {
+        let url = self.endpoint(&["board", &self.board_name, "keys"]);
+        let req = self.with_api_key(self.client.post(url)).json(key);
+        let response = self.send(req).await?;
+        Ok(response.json().await?)
+    }
+
+    /// Revoke the key, it stops working immediately.
+    /// Requires the admin key.
+    pub async fn revoke_key(&self, key_id: i32) -> Result<()> {
+        let url = self.endpoint(&["board", &self.board_name, "keys", &key_id.to_string()]);
+        let req = self.with_api_key(self.client.delete(url));
+        self.send(req).await?;
+        Ok(())
+    }
+
+    /// Replace the key with a newly generated one.
+    /// The old key stops working immediately.
+    /// Requires the admin key.
+    pub async fn rotate_key(&self, key_id: i32) -> Result<NewKey> {
+        let url = self.endpoint(&[
+            "board",
+            &self.board_name,
+            "keys",
+            &key_id.to_string(),
+            "rotate",
+        ]);
+        let req = self.with_api_key(self.client.post(url));
+        let response = self.send(req).await?;
+        Ok(response.json().await?)
+    }
+
+    /// Fetch scores from the board.
+    /// Use [`ScoreQuery::default`] to get all scores, highest first.
+    pub async fn fetch_scores(&self, query: &ScoreQuery) -> Result<ScoresPage> {
+        let req = self.with_api_key(self.client.get(self.board_url()).query(query));
+        let response = self.send(req).await?;
+        Ok(response.json().await?)
+    }
+
+    /// Fetch the rank of the player on the board together with
+    /// `window` scores above and below them.
+    pub async fn fetch_rank(&self, player_id: i32, window: u32) -> Result<PlayerRank> {
+        let url = self.endpoint(&["board", &self.board_name, "player", &player_id.to_string()]);
+        let req = self
+            .with_api_key(self.client.get(url))
+            .query(&[("window", window)]);
+        let response = self.send(req).await?;
+        Ok(response.json().await?)
+    }
+
+    /// List the scores stored on the board, including hidden ones.
+    /// Requires the admin key.
+    pub async fn list_records(&self, query: &RecordQuery) -> Result<Vec<ScoreRecord>> {
+        let url = self.endpoint(&["board", &self.board_name, "scores"]);
+        let req = self.with_api_key(self.client.get(url)).query(query);
+        let response = self.send(req).await?;
+        Ok(response.json().await?)
+    }
+
+    /// Hide or unflag a score, returns the updated record.
+    /// Requires the admin key.
+    pub async fn update_score(&self, score_id: i32, update: &ScoreUpdate) -> Result<ScoreRecord> {
+        let url = self.endpoint(&["board", &self.board_name, "scores", &score_id.to_string()]);
+        let req = self.with_api_key(self.client.patch(url)).json(update);
+        let response = self.send(req).await?;
+        Ok(response.json().await?)
+    }
+
+    /// Delete a single score from the board.
+    /// Requires the admin key.
+    pub async fn delete_score(&self, score_id: i32) -> Result<()> {
+        let url = self.endpoint(&["board", &self.board_name, "scores", &score_id.to_string()]);
+        let req = self.with_api_key(self.client.delete(url));
+        self.send(req).await?;
+        Ok(())
+    }
+
+    /// Delete all scores of the player from the board.
+    /// Requires the admin key.
+    pub async fn wipe_player_scores(&self, player_id: i32) -> Result<()> {
+        let url = self.endpoint(&[
+            "board",
+            &self.board_name,
+            "player",
+            &player_id.to_string(),
+            "scores",
+        ]);
+        let req = self.with_api_key(self.client.delete(url));
+        self.send(req).await?;
+        Ok(())
+    }
+
+    /// List the players banned from the board.
+    /// Requires the admin key.
+    pub async fn list_bans(&self) -> Result<Vec<BanInfo>> {
+        let url = self.endpoint(&["board", &self.board_name, "bans"]);
+        let req = self.with_api_key(self.client.get(url));
+        let response = self.send(req).await?;
+        Ok(response.json().await?)
+    }
+
+    /// Ban the player from submitting to the board.
+    /// Requires the admin key.
+    pub async fn ban_player(&self, player_id: i32) -> Result<()> {
+        let url = self.endpoint(&["board", &self.board_name, "bans", &player_id.to_string()]);
+        let req = self.with_api_key(self.client.put(url));
+        self.send(req).await?;
+        Ok(())
+    }
+
+    /// Lift the ban of the player.
+    /// Fails with [`Error::NoSuchPlayer`] if the player is not banned.
+    /// Requires the admin key.
+    pub async fn unban_player(&self, player_id: i32) -> Result<()> {
+        let url = self.endpoint(&["board", &self.board_name, "bans", &player_id.to_string()]);
+        let req = self.with_api_key(self.client.delete(url));
+        self.send(req).await?;
+        Ok(())
+    }
+
+    /// Require submissions to the board to be signed with a newly generated secret.
+    /// Calling it again replaces the secret.
+    /// Requires the admin key.
+    pub async fn enable_signing(&self) -> Result<SigningSecret> {
+        let url = self.endpoint(&["board", &self.board_name, "signing"]);
+        let req = self.with_api_key(self.client.post(url));
+        let response = self.send(req).await?;
+        Ok(response.json().await?)
+    }
+
+    /// Stop requiring signed submissions.
+    /// Requires the admin key.
+    pub async fn disable_signing(&self) -> Result<()> {
+        let url = self.endpoint(&["board", &self.board_name, "signing"]);
+        let req = self.with_api_key(self.client.delete(url));
+        self.send(req).await?;
+        Ok(())
+    }
+
+    /// Submit the score of the player.
+    /// The submission is signed if the signing secret is set.
+    pub async fn submit_score(&self, player: &Player, entry: &ScoreEntry) -> Result<SubmitResult> {
         let mut req = self
             .client
-            .post(self.url.clone())
+            .post(self.board_url())
             .query(&[("player_id", player.id)])
             .header("player-key", &player.key);
-        if let Some(key) = &self.api_key {
-            req = req.header("api-key", key);
+        if let Some(secret) = &self.signing_secret {
+            req = self.sign(req, secret, player.id, entry);
         }
+        let req = self.with_api_key(req).json(entry);
 
-        let req = req.json(entry);
-
-        let _response = req.send().await?;
-        // TODO: check returned error
-        Ok(())
+        let response = self.send(req).await?;
+        Ok(response.json().await?)
     }
 }
@@ -1,12 +1,21 @@
+pub mod signing;
+
 use serde::{Deserialize, Serialize};
 
 pub type Score = i32;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct ScoreEntry {
+    /// Name of the player.
+    /// Ignored when submitting, use the rename endpoint to change it.
+    #[serde(default)]
     pub player: String,
     pub score: Score,
     pub extra_info: Option<String>,
+    /// Unix timestamp (in seconds) of the submission.
+    /// Set by the server, ignored when submitting.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub submitted_at: Option<i64>,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -15,4 +24,437 @@ pub struct Player {
     /// Secret key used to authenticate.
     pub key: String,
     pub name: String,
+    /// One-time code used to recover the player if the key is lost.
+    /// Only returned when the player is created or recovered.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recovery_code: Option<String>,
+}
+
+/// A past change of a player name.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct NameChange {
+    pub old_name: String,
+    pub new_name: String,
+    /// Unix timestamp (in seconds) of the change.
+    pub renamed_at: i64,
+}
+
+/// Keys generated for a newly created board.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BoardKeys {
+    pub read: String,
+    pub submit: String,
+    pub admin: String,
+}
+
+/// Access level granted by a board key.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyAuthority {
+    /// Allows fetching scores.
+    Read,
+    /// Allows fetching and submitting scores.
+    Submit,
+    /// Allows full control over the board.
+    Admin,
+}
+
+/// Request body for minting a new board key.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyCreate {
+    /// Name to tell the keys apart, e.g. the game build using it.
+    pub label: String,
+    pub authority: KeyAuthority,
+    /// Unix timestamp (in seconds) after which the key stops working.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<i64>,
+}
+
+/// Public information about a board key.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct KeyInfo {
+    pub id: i32,
+    pub label: String,
+    pub authority: KeyAuthority,
+    /// Unix timestamp (in seconds) of when the key was generated.
+    pub created_at: i64,
+    pub expires_at: Option<i64>,
+    /// Unix timestamp (in seconds) of the last request made with the key.
+    pub last_used_at: Option<i64>,
+}
+
+/// A newly generated board key.
+/// The key itself is only shown once.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewKey {
+    #[serde(flatten)]
+    pub info: KeyInfo,
+    pub key: String,
+}
+
+/// Machine-readable kind of an error returned by the server.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    Unauthorized,
+    Forbidden,
+    InvalidPlayer,
+    /// The player name does not follow the rules of the server.
+    InvalidPlayerName,
+    /// The text contains words blocked by the server.
+    RejectedText,
+    InvalidBoardName,
+    BoardAlreadyExists,
+    NoSuchBoard,
+    /// The player has no scores on the board.
+    NoPlayerScore,
+    NoSuchKey,
+    NoSuchScore,
+    NoSuchPlayer,
+    /// The player is banned from submitting to the board.
+    PlayerBanned,
+    /// The score does not follow the validation rules of the board.
+    ScoreRejected,
+    /// The board settings are inconsistent.
+    InvalidSettings,
+    /// The submission signature is missing, invalid, expired or reused.
+    InvalidSignature,
+    /// Cannot remove the only admin key of a board.
+    LastAdminKey,
+    /// The client has sent too many requests, retry later.
+    TooManyRequests,
+    /// The operator has created as many boards as allowed.
+    QuotaExceeded,
+    /// The request is malformed: missing headers, invalid body or query.
+    InvalidRequest,
+    /// No route matches the request path.
+    NotFound,
+    MethodNotAllowed,
+    Internal,
+    /// An error code not known to this version of the library.
+    #[serde(other)]
+    Unknown,
+}
+
+/// Body of an error response returned by the server.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ErrorResponse {
+    pub code: ErrorCode,
+    /// Human-readable description of the error.
+    pub message: String,
+    /// Additional information about the cause, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<String>,
+}
+
+/// Order in which scores are listed.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ScoreOrder {
+    /// Lowest scores first.
+    Ascending,
+    /// Highest scores first.
+    #[default]
+    Descending,
+}
+
+/// Query parameters for fetching scores from a board.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScoreQuery {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order: Option<ScoreOrder>,
+    /// Maximum number of scores to return.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+    /// Number of scores to skip from the top.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset: Option<u32>,
+    /// Only include scores submitted during the current period.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub period: Option<TimePeriod>,
+    /// Only include scores submitted at or after this unix timestamp (in seconds).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub since: Option<i64>,
+    /// Only include scores submitted before this unix timestamp (in seconds).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<i64>,
+}
+
+/// A calendar period in UTC, starting at midnight.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TimePeriod {
+    Today,
+    /// The week starting on Monday.
+    Week,
+    Month,
+}
+
+/// A page of scores from a board.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ScoresPage {
+    /// Total number of scores shown on the board in the time range.
+    pub total: u64,
+    pub scores: Vec<ScoreEntry>,
+}
+
+/// Which scores of a single player are shown on a board.
+/// Every submitted score is stored, and the policy is applied within
+/// the requested time range, e.g. the best score of each player this week.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ScorePolicy {
+    /// Show every submitted score.
+    #[default]
+    All,
+    /// Show only the best score of each player.
+    Best,
+    /// Show only the latest score of each player.
+    Latest,
+}
+
+/// Settings of a board chosen at creation.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BoardSettings {
+    /// Order in which scores are ranked, best first.
+    /// Use [`ScoreOrder::Ascending`] for boards where lower is better.
+    #[serde(default)]
+    pub order: ScoreOrder,
+    #[serde(default)]
+    pub policy: ScorePolicy,
+    /// Whether scores can be read without an api key.
+    #[serde(default)]
+    pub public_read: bool,
+    #[serde(default)]
+    pub validation: ScoreValidation,
+}
+
+/// Rules every submitted score has to follow.
+/// Rules left as `None` are not checked.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ScoreValidation {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_score: Option<Score>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_score: Option<Score>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub submission_limit: Option<SubmissionLimit>,
+    /// Maximum length of the extra info in characters.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_info_length: Option<u32>,
+}
+
+/// Maximum number of submissions by a single player during a time window.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SubmissionLimit {
+    pub count: u32,
+    /// Length of the window in seconds.
+    pub window: u32,
+}
+
+/// Request body for changing board settings.
+/// Fields left as `None` are not changed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BoardSettingsUpdate {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_read: Option<bool>,
+    /// Replaces all validation rules.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub validation: Option<ScoreValidation>,
+}
+
+/// Request body for creating a board.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BoardCreate {
+    pub name: String,
+    #[serde(default)]
+    pub settings: BoardSettings,
+}
+
+impl ScoreOrder {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Ascending => "ascending",
+            Self::Descending => "descending",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "ascending" => Some(Self::Ascending),
+            "descending" => Some(Self::Descending),
+            _ => None,
+        }
+    }
+
+    /// Checks whether score `a` is ranked strictly higher than `b`.
+    pub fn is_better(&self, a: Score, b: Score) -> bool {
+        match self {
+            Self::Ascending => a < b,
+            Self::Descending => a > b,
+        }
+    }
+}
+
+impl KeyAuthority {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Read => "read",
+            Self::Submit => "submit",
+            Self::Admin => "admin",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "read" => Some(Self::Read),
+            "submit" => Some(Self::Submit),
+            "admin" => Some(Self::Admin),
+            _ => None,
+        }
+    }
+}
+
+impl ScorePolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::All => "all",
+            Self::Best => "best",
+            Self::Latest => "latest",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "all" => Some(Self::All),
+            "best" => Some(Self::Best),
+            "latest" => Some(Self::Latest),
+            _ => None,
+        }
+    }
+}
+
+/// Position of a player on a board.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PlayerRank {
+    /// Rank of the best score of the player, starting from 1.
+    pub rank: u64,
+    /// Best score of the player.
+    pub score: ScoreEntry,
+    /// Scores ranked right above the player, best first.
+    pub above: Vec<ScoreEntry>,
+    /// Scores ranked right below the player, best first.
+    pub below: Vec<ScoreEntry>,
+}
+
+/// Result of submitting a score to a board.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SubmitResult {
+    /// Id of the stored score.
+    /// Every score is stored, even if the board policy shows a better one.
+    pub score_id: i32,
+    /// Rank of the player on the board after the submission.
+    pub rank: u64,
+    /// Best score of the player on the board before the submission.
+    pub previous_best: Option<Score>,
+    /// Whether the score beats the previous best.
+    pub improved: bool,
+}
+
+/// A score as stored on the board, for moderation.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ScoreRecord {
+    pub id: i32,
+    pub player_id: i32,
+    pub player_name: String,
+    pub score: Score,
+    pub extra_info: Option<String>,
+    /// Unix timestamp (in seconds) of the submission.
+    pub submitted_at: i64,
+    /// Hidden scores are kept but not shown on the board.
+    pub hidden: bool,
+    /// Whether the score was flagged for moderation by the content filter.
+    pub flagged: bool,
+}
+
+/// Query parameters for listing score records.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RecordQuery {
+    /// Only include scores of this player.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub player_id: Option<i32>,
+    /// Only include flagged scores.
+    #[serde(default)]
+    pub flagged: bool,
+    /// Maximum number of records to return.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+    /// Number of records to skip.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset: Option<u32>,
+}
+
+/// Request body for moderating a score.
+/// Fields left as `None` are not changed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScoreUpdate {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hidden: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flagged: Option<bool>,
+}
+
+/// A player banned from submitting to a board.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BanInfo {
+    pub player_id: i32,
+    pub player_name: String,
+    /// Unix timestamp (in seconds) of the ban.
+    pub banned_at: i64,
+}
+
+/// Secret used to sign submissions to a board.
+/// The secret is only shown once.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SigningSecret {
+    pub secret: String,
+}
+
+/// Information about a board and its settings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BoardInfo {
+    pub name: String,
+    /// Human-readable name to show instead of the board name.
+    pub display_name: Option<String>,
+    pub description: Option<String>,
+    /// Unix timestamp (in seconds) of the creation,
+    /// unknown for boards created by older server versions.
+    pub created_at: Option<i64>,
+    /// Number of visible scores submitted to the board,
+    /// including the ones not shown because of the board policy.
+    pub score_count: i64,
+    /// Number of players with visible scores on the board.
+    pub player_count: i64,
+    pub settings: BoardSettings,
+}
+
+/// Query parameters for listing boards.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BoardQuery {
+    /// Maximum number of boards to return.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+    /// Number of boards to skip.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset: Option<u32>,
+}
+
+/// Request body for changing the description of a board.
+/// Fields left as `None` are not changed, empty strings remove the value.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BoardInfoUpdate {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
 }
@@ -0,0 +1,81 @@
+//! Signing of score submissions with a secret shared by the game and the server.
+
+use crate::ScoreEntry;
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+/// Header with the hex-encoded signature.
+pub const SIGNATURE_HEADER: &str = "submission-signature";
+/// Header with the unix timestamp (in seconds) of the submission.
+pub const TIMESTAMP_HEADER: &str = "submission-timestamp";
+/// Header with a random string unique to the submission.
+pub const NONCE_HEADER: &str = "submission-nonce";
+
+/// Maximum length of a nonce in bytes.
+pub const MAX_NONCE_LENGTH: usize = 64;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Builds the message signed for a submission.
+/// Strings are length-prefixed so that different submissions never produce the same message.
+pub fn submission_message(
+    board_name: &str,
+    player_id: i32,
+    entry: &ScoreEntry,
+    timestamp: i64,
+    nonce: &str,
+) -> String {
+    let extra_info = match &entry.extra_info {
+        None => "-".to_owned(),
+        Some(info) => format!("{}:{}", info.len(), info),
+    };
+    format!(
+        "nertboard-v1\n{}:{}\n{}\n{}\n{}\n{}\n{}:{}",
+        board_name.len(),
+        board_name,
+        player_id,
+        entry.score,
+        extra_info,
+        timestamp,
+        nonce.len(),
+        nonce
+    )
+}
+
+/// Signs the message and returns the hex-encoded signature.
+pub fn sign(secret: &str, message: &str) -> String {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).expect("hmac accepts any key");
+    mac.update(message.as_bytes());
+    to_hex(mac.finalize().into_bytes())
+}
+
+/// Checks the hex-encoded signature of the message in constant time.
+pub fn verify(secret: &str, message: &str, signature: &str) -> bool {
+    let Some(signature) = from_hex(signature) else {
+        return false;
+    };
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).expect("hmac accepts any key");
+    mac.update(message.as_bytes());
+    mac.verify_slice(&signature).is_ok()
+}
+
+/// Encodes the bytes as lowercase hex.
+pub fn to_hex(bytes: impl AsRef<[u8]>) -> String {
+    bytes
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Decodes a hex string, `None` if it is not valid hex.
+pub fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
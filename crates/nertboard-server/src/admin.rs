@@ -0,0 +1,461 @@
+//! Commands that manage the database directly, without a running server.
+
+use crate::{
+    config::Config,
+    database::{DatabasePool, Id},
+    prelude::*,
+    server,
+};
+
+use color_eyre::eyre::bail;
+use nertboard_core::{
+    BoardCreate, BoardSettings, RecordQuery, ScoreOrder, ScorePolicy, ScoreRecord,
+};
+use sqlx::{any::AnyRow, Row};
+use std::{io::Write, path::PathBuf};
+
+#[derive(Debug, Clone, clap::Subcommand)]
+pub enum Command {
+    /// Run the server, the default if no command is given.
+    Serve,
+    /// Manage boards.
+    #[command(subcommand)]
+    Board(BoardCommand),
+    /// Manage players.
+    #[command(subcommand)]
+    Player(PlayerCommand),
+    /// Manage scores.
+    #[command(subcommand)]
+    Scores(ScoresCommand),
+}
+
+#[derive(Debug, Clone, clap::Subcommand)]
+pub enum BoardCommand {
+    /// Create a board and print its keys.
+    Create {
+        name: String,
+        /// Order in which scores are ranked: ascending or descending.
+        #[arg(long, default_value = "descending", value_parser = parse_order)]
+        order: ScoreOrder,
+        /// Which scores of a player are kept: all, best or latest.
+        #[arg(long, default_value = "all", value_parser = parse_policy)]
+        policy: ScorePolicy,
+        /// Allow reading scores without an api key.
+        #[arg(long)]
+        public_read: bool,
+    },
+    /// List all boards.
+    List,
+    /// Delete a board together with its scores and keys.
+    Delete {
+        name: String,
+        /// Confirm the deletion.
+        #[arg(long)]
+        yes: bool,
+    },
+    /// Replace every key of a board and print the new ones.
+    RotateKeys { name: String },
+}
+
+#[derive(Debug, Clone, clap::Subcommand)]
+pub enum PlayerCommand {
+    /// List all players.
+    List {
+        /// Only list players flagged by the content filter.
+        #[arg(long)]
+        flagged: bool,
+    },
+    /// Ban a player from submitting to a board.
+    Ban { board: String, player_id: Id },
+    /// Lift the ban of a player on a board.
+    Unban { board: String, player_id: Id },
+}
+
+#[derive(Debug, Clone, clap::Subcommand)]
+pub enum ScoresCommand {
+    /// Export all scores of a board, including hidden ones.
+    Export {
+        board: String,
+        #[arg(long, value_enum, default_value_t)]
+        format: ExportFormat,
+        /// File to write to instead of the standard output.
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ExportFormat {
+    /// A json array of score records.
+    #[default]
+    Json,
+    /// Comma-separated values with a header row.
+    Csv,
+}
+
+fn parse_order(s: &str) -> Result<ScoreOrder, String> {
+    ScoreOrder::parse(s).ok_or_else(|| format!("unknown score order: {}", s))
+}
+
+fn parse_policy(s: &str) -> Result<ScorePolicy, String> {
+    ScorePolicy::parse(s).ok_or_else(|| format!("unknown score policy: {}", s))
+}
+
+/// Runs the command, writing the results to `out`.
+pub async fn run(
+    database: &DatabasePool,
+    config: &Config,
+    command: Command,
+    out: &mut impl Write,
+) -> Result<()> {
+    match command {
+        Command::Serve => bail!("the server is not an admin command"),
+        Command::Board(command) => run_board(database, config, command, out).await,
+        Command::Player(command) => run_player(database, command, out).await,
+        Command::Scores(command) => run_scores(database, command, out).await,
+    }
+}
+
+async fn run_board(
+    database: &DatabasePool,
+    config: &Config,
+    command: BoardCommand,
+    out: &mut impl Write,
+) -> Result<()> {
+    match command {
+        BoardCommand::Create {
+            name,
+            order,
+            policy,
+            public_read,
+        } => {
+            let board = BoardCreate {
+                name,
+                settings: BoardSettings {
+                    order,
+                    policy,
+                    public_read,
+                    ..Default::default()
+                },
+            };
+            let keys = server::insert_board(database, &config.key_lengths, board, None).await?;
+            writeln!(out, "read key: {}", keys.read)?;
+            writeln!(out, "submit key: {}", keys.submit)?;
+            writeln!(out, "admin key: {}", keys.admin)?;
+        }
+        BoardCommand::List => {
+            let boards = sqlx::query(
+                "
+SELECT board_name, score_order, score_policy, public_read, created_by,
+    COUNT(score_id) AS scores, COUNT(DISTINCT player_id) AS players
+FROM boards
+LEFT JOIN scores ON boards.board_id = scores.board_id
+GROUP BY boards.board_id, board_name, score_order, score_policy, public_read, created_by
+ORDER BY board_name
+                ",
+            )
+            .fetch_all(database)
+            .await?;
+
+            writeln!(
+                out,
+                "{:<32} {:<10} {:<8} {:<6} {:>8} {:>8}  creator",
+                "name", "order", "policy", "public", "scores", "players"
+            )?;
+            for row in boards {
+                writeln!(
+                    out,
+                    "{:<32} {:<10} {:<8} {:<6} {:>8} {:>8}  {}",
+                    row.try_get::<String, _>("board_name")?,
+                    row.try_get::<String, _>("score_order")?,
+                    row.try_get::<String, _>("score_policy")?,
+                    row.try_get::<i32, _>("public_read")? != 0,
+                    row.try_get::<i64, _>("scores")?,
+                    row.try_get::<i64, _>("players")?,
+                    row.try_get::<String, _>("created_by")
+                        .unwrap_or_else(|_| "-".to_owned()),
+                )?;
+            }
+        }
+        BoardCommand::Delete { name, yes } => {
+            let board = server::find_board(database, &name).await?;
+            if !yes {
+                bail!(
+                    "deleting the board {} cannot be undone, pass --yes to confirm",
+                    name
+                );
+            }
+            server::remove_board(database, board.id).await?;
+            writeln!(out, "deleted board {}", name)?;
+        }
+        BoardCommand::RotateKeys { name } => {
+            let board = server::find_board(database, &name).await?;
+            // Either every key is replaced or none, so that no new key is lost
+            let mut transaction = database.begin().await?;
+            let mut keys = Vec::new();
+            for info in server::fetch_keys(&mut *transaction, board.id).await? {
+                keys.push(server::replace_key(&mut *transaction, &config.key_lengths, info).await?);
+            }
+            transaction.commit().await?;
+
+            for key in keys {
+                writeln!(
+                    out,
+                    "{} ({}): {}",
+                    key.info.label,
+                    key.info.authority.as_str(),
+                    key.key
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn run_player(
+    database: &DatabasePool,
+    command: PlayerCommand,
+    out: &mut impl Write,
+) -> Result<()> {
+    match command {
+        PlayerCommand::List { flagged } => {
+            let players = sqlx::query(
+                "
+SELECT players.player_id, name, players.flagged, COUNT(score_id) AS scores
+FROM players
+LEFT JOIN scores ON players.player_id = scores.player_id
+WHERE (? OR players.flagged <> 0)
+GROUP BY players.player_id, name, players.flagged
+ORDER BY players.player_id
+                ",
+            )
+            .bind(!flagged)
+            .try_map(|row: AnyRow| {
+                Ok((
+                    row.try_get::<Id, _>("player_id")?,
+                    row.try_get::<String, _>("name")?,
+                    row.try_get::<i32, _>("flagged")? != 0,
+                    row.try_get::<i64, _>("scores")?,
+                ))
+            })
+            .fetch_all(database)
+            .await?;
+
+            writeln!(
+                out,
+                "{:>8} {:<32} {:<7} {:>8}",
+                "id", "name", "flagged", "scores"
+            )?;
+            for (id, name, flagged, scores) in players {
+                writeln!(out, "{:>8} {:<32} {:<7} {:>8}", id, name, flagged, scores)?;
+            }
+        }
+        PlayerCommand::Ban { board, player_id } => {
+            let board = server::find_board(database, &board).await?;
+            server::insert_ban(database, board.id, player_id).await?;
+            writeln!(out, "banned player {}", player_id)?;
+        }
+        PlayerCommand::Unban { board, player_id } => {
+            let board = server::find_board(database, &board).await?;
+            let deleted =
+                sqlx::query("DELETE FROM board_bans WHERE board_id = ? AND player_id = ?")
+                    .bind(board.id)
+                    .bind(player_id)
+                    .execute(database)
+                    .await?
+                    .rows_affected();
+            if deleted == 0 {
+                bail!("player {} is not banned from the board", player_id);
+            }
+            writeln!(out, "unbanned player {}", player_id)?;
+        }
+    }
+    Ok(())
+}
+
+async fn run_scores(
+    database: &DatabasePool,
+    command: ScoresCommand,
+    out: &mut impl Write,
+) -> Result<()> {
+    match command {
+        ScoresCommand::Export {
+            board,
+            format,
+            output,
+        } => {
+            let board = server::find_board(database, &board).await?;
+            let records = server::fetch_records(
+                database,
+                board.id,
+                &RecordQuery {
+                    player_id: None,
+                    flagged: false,
+                    limit: None,
+                    offset: None,
+                },
+            )
+            .await?;
+
+            let mut file;
+            let out: &mut dyn Write = match &output {
+                Some(path) => {
+                    file = std::fs::File::create(path)
+                        .with_context(|| format!("when creating {}", path.display()))?;
+                    &mut file
+                }
+                None => out,
+            };
+            match format {
+                ExportFormat::Json => {
+                    serde_json::to_writer_pretty(&mut *out, &records)?;
+                    writeln!(out)?;
+                }
+                ExportFormat::Csv => write_csv(out, &records)?,
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_csv(out: &mut dyn Write, records: &[ScoreRecord]) -> Result<()> {
+    /// Quotes the field if it contains special characters.
+    fn escape(field: &str) -> String {
+        if field.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", field.replace('"', "\"\""))
+        } else {
+            field.to_owned()
+        }
+    }
+
+    writeln!(
+        out,
+        "id,player_id,player_name,score,extra_info,submitted_at,hidden,flagged"
+    )?;
+    for record in records {
+        writeln!(
+            out,
+            "{},{},{},{},{},{},{},{}",
+            record.id,
+            record.player_id,
+            escape(&record.player_name),
+            record.score,
+            escape(record.extra_info.as_deref().unwrap_or_default()),
+            record.submitted_at,
+            record.hidden,
+            record.flagged
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn run_command(database: &DatabasePool, command: Command) -> Result<String> {
+        let mut out = Vec::new();
+        run(database, &Config::default(), command, &mut out).await?;
+        Ok(String::from_utf8(out)?)
+    }
+
+    #[tokio::test]
+    async fn test_admin_commands() -> Result<()> {
+        crate::setup::setup_test();
+        let database = crate::setup::connect_database("sqlite::memory:", 1).await?;
+
+        let created = run_command(
+            &database,
+            Command::Board(BoardCommand::Create {
+                name: "test-table".to_owned(),
+                order: ScoreOrder::Ascending,
+                policy: ScorePolicy::Best,
+                public_read: false,
+            }),
+        )
+        .await?;
+        assert_eq!(created.lines().count(), 3);
+
+        let list = run_command(&database, Command::Board(BoardCommand::List)).await?;
+        assert!(list.contains("test-table"));
+        assert!(list.contains("ascending"));
+
+        let rotated = run_command(
+            &database,
+            Command::Board(BoardCommand::RotateKeys {
+                name: "test-table".to_owned(),
+            }),
+        )
+        .await?;
+        assert_eq!(rotated.lines().count(), 3);
+        for (old, new) in created.lines().zip(rotated.lines()) {
+            let key = |line: &str| line.rsplit(' ').next().unwrap_or_default().to_owned();
+            assert_ne!(key(old), key(new));
+        }
+
+        // Players are only created through the server
+        let player_id = sqlx::query(
+            "INSERT INTO players (player_key, name) VALUES ('key', 'nertsal') RETURNING player_id",
+        )
+        .try_map(|row: AnyRow| row.try_get::<Id, _>("player_id"))
+        .fetch_one(&database)
+        .await?;
+        let board = server::find_board(&database, "test-table").await?;
+        sqlx::query(
+            "
+INSERT INTO scores (board_id, player_id, score, extra_info, submitted_at)
+VALUES (?, ?, 10, 'first, \"best\"', 0)
+            ",
+        )
+        .bind(board.id)
+        .bind(player_id)
+        .execute(&database)
+        .await?;
+
+        let ban = |player_id| {
+            Command::Player(PlayerCommand::Ban {
+                board: "test-table".to_owned(),
+                player_id,
+            })
+        };
+        run_command(&database, ban(player_id)).await?;
+        assert!(run_command(&database, ban(player_id + 1)).await.is_err());
+
+        let unban = || {
+            Command::Player(PlayerCommand::Unban {
+                board: "test-table".to_owned(),
+                player_id,
+            })
+        };
+        run_command(&database, unban()).await?;
+        assert!(run_command(&database, unban()).await.is_err());
+        run_command(&database, ban(player_id)).await?;
+
+        let csv = run_command(
+            &database,
+            Command::Scores(ScoresCommand::Export {
+                board: "test-table".to_owned(),
+                format: ExportFormat::Csv,
+                output: None,
+            }),
+        )
+        .await?;
+        assert_eq!(
+            csv.lines().nth(1),
+            Some(r#"1,1,nertsal,10,"first, ""best""",0,false,false"#)
+        );
+
+        let delete = |yes| {
+            Command::Board(BoardCommand::Delete {
+                name: "test-table".to_owned(),
+                yes,
+            })
+        };
+        assert!(run_command(&database, delete(false)).await.is_err());
+        run_command(&database, delete(true)).await?;
+        let list = run_command(&database, Command::Board(BoardCommand::List)).await?;
+        assert!(!list.contains("test-table"));
+
+        Ok(())
+    }
+}
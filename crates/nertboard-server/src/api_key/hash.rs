@@ -0,0 +1,53 @@
+use nertboard_core::signing::{from_hex, to_hex};
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+
+const SCHEME: &str = "sha256";
+const SALT_LENGTH: usize = 16;
+
+/// A salted hash of a key, as stored in the database.
+/// Formatted as `sha256$<salt>$<hash>` with the salt and the hash hex-encoded.
+/// Only meant for long random keys, see [`super::MIN_HASHED_KEY_LENGTH`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HashedKey(Box<str>);
+
+impl HashedKey {
+    /// Hashes the key with a fresh random salt.
+    pub fn hash(key: &str) -> Self {
+        let salt: [u8; SALT_LENGTH] = rand::thread_rng().gen();
+        let hash = digest(&salt, key);
+        Self(format!("{}${}${}", SCHEME, to_hex(salt), to_hex(hash)).into())
+    }
+
+    /// Wraps a hash loaded from the database.
+    pub fn from_stored(hash: impl Into<Box<str>>) -> Self {
+        Self(hash.into())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Checks whether the key matches the hash in constant time.
+    pub fn verify(&self, key: &str) -> bool {
+        let mut parts = self.0.split('$');
+        let (Some(SCHEME), Some(salt), Some(hash), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return false;
+        };
+        let (Some(salt), Some(hash)) = (from_hex(salt), from_hex(hash)) else {
+            return false;
+        };
+
+        digest(&salt, key).as_ref().ct_eq(&hash).into()
+    }
+}
+
+fn digest(salt: &[u8], key: &str) -> impl AsRef<[u8]> {
+    let mut hasher = Sha256::new();
+    hasher.update(salt);
+    hasher.update(key.as_bytes());
+    hasher.finalize()
+}
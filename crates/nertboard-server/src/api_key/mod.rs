@@ -1,16 +1,21 @@
-use axum::http::{request::Parts, StatusCode};
+mod hash;
+
+pub use self::hash::HashedKey;
+
+use crate::{database::RequestError, server::KeyBudget};
+
+use axum::http::request::Parts;
+use nertboard_core::KeyAuthority;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize)]
-pub struct StringKey(Box<str>);
+/// Shortest generated key that is stored hashed, about 128 bits of entropy.
+/// Keys are random, unlike passwords, so with this much entropy a single salted
+/// hash cannot be brute-forced from a leaked database, and no slow hash is needed.
+pub const MIN_HASHED_KEY_LENGTH: usize = 22;
 
 #[derive(Serialize, Deserialize)]
-pub struct BoardKeys {
-    pub read: StringKey,
-    pub submit: StringKey,
-    pub admin: StringKey,
-}
+pub struct StringKey(Box<str>);
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum AuthorityLevel {
@@ -21,10 +26,6 @@ pub enum AuthorityLevel {
 }
 
 impl StringKey {
-    pub fn new(key: impl Into<Box<str>>) -> Self {
-        Self(key.into())
-    }
-
     pub fn inner(&self) -> &str {
         &self.0
     }
@@ -40,40 +41,35 @@ impl StringKey {
     }
 }
 
-impl BoardKeys {
-    pub fn generate() -> Self {
-        Self {
-            read: StringKey::generate(10),
-            submit: StringKey::generate(10),
-            admin: StringKey::generate(20),
-        }
-    }
-
-    pub fn check_authority(&self, key: &str) -> AuthorityLevel {
-        if key == self.read.inner() {
-            AuthorityLevel::Read
-        } else if key == self.submit.inner() {
-            AuthorityLevel::Submit
-        } else if key == self.admin.inner() {
-            AuthorityLevel::Admin
-        } else {
-            AuthorityLevel::Unauthorized
+impl From<KeyAuthority> for AuthorityLevel {
+    fn from(authority: KeyAuthority) -> Self {
+        match authority {
+            KeyAuthority::Read => Self::Read,
+            KeyAuthority::Submit => Self::Submit,
+            KeyAuthority::Admin => Self::Admin,
         }
     }
 }
 
-pub struct ApiKey(pub String);
+pub struct ApiKey {
+    pub key: String,
+    /// Rate limit to charge once the key is verified, if the request has one.
+    pub budget: Option<KeyBudget>,
+}
 
 #[axum::async_trait]
 impl<S> axum::extract::FromRequestParts<S> for ApiKey {
-    type Rejection = (StatusCode, &'static str);
+    type Rejection = RequestError;
 
     async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
         match parts.headers.get("api-key") {
-            None => Err((StatusCode::BAD_REQUEST, "api key missing")),
+            None => Err(RequestError::InvalidHeader("api key missing")),
             Some(key) => match key.to_str() {
-                Ok(key) => Ok(Self(key.to_string())),
-                Err(_) => Err((StatusCode::BAD_REQUEST, "api key is invalid")),
+                Ok(key) => Ok(Self {
+                    key: key.to_string(),
+                    budget: parts.extensions.get::<KeyBudget>().cloned(),
+                }),
+                Err(_) => Err(RequestError::InvalidHeader("api key is invalid")),
             },
         }
     }
@@ -83,14 +79,32 @@ pub struct PlayerKey(pub String);
 
 #[axum::async_trait]
 impl<S> axum::extract::FromRequestParts<S> for PlayerKey {
-    type Rejection = (StatusCode, &'static str);
+    type Rejection = RequestError;
 
     async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
         match parts.headers.get("player-key") {
-            None => Err((StatusCode::BAD_REQUEST, "player key missing")),
+            None => Err(RequestError::InvalidHeader("player key missing")),
+            Some(key) => match key.to_str() {
+                Ok(key) => Ok(Self(key.to_string())),
+                Err(_) => Err(RequestError::InvalidHeader("player key is invalid")),
+            },
+        }
+    }
+}
+
+/// Server-level key of an operator allowed to create boards.
+pub struct OperatorKey(pub String);
+
+#[axum::async_trait]
+impl<S> axum::extract::FromRequestParts<S> for OperatorKey {
+    type Rejection = RequestError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        match parts.headers.get("operator-key") {
+            None => Err(RequestError::InvalidHeader("operator key missing")),
             Some(key) => match key.to_str() {
                 Ok(key) => Ok(Self(key.to_string())),
-                Err(_) => Err((StatusCode::BAD_REQUEST, "player key is invalid")),
+                Err(_) => Err(RequestError::InvalidHeader("operator key is invalid")),
             },
         }
     }
@@ -0,0 +1,596 @@
+use crate::{api_key::MIN_HASHED_KEY_LENGTH, filter::FilterMode, prelude::*};
+
+use axum::http::HeaderValue;
+use color_eyre::eyre::bail;
+use nertboard_core::KeyAuthority;
+use serde::Deserialize;
+use std::{
+    net::{IpAddr, Ipv4Addr},
+    path::{Path, PathBuf},
+};
+use subtle::ConstantTimeEq;
+
+/// Shortest length accepted in the configuration for keys that are not hashed.
+const MIN_KEY_LENGTH: usize = 8;
+/// Longest key length accepted in the configuration.
+const MAX_KEY_LENGTH: usize = 128;
+/// Longest operator name, limited by the database column.
+const MAX_OPERATOR_NAME_LENGTH: usize = 64;
+
+/// Name of the operator configured with `--operator-key`.
+const CLI_OPERATOR_NAME: &str = "cli";
+
+/// Configuration of the server.
+/// Loaded from a TOML file, every section is optional.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub names: NameRules,
+    pub filter: FilterConfig,
+    pub rate_limits: RateLimits,
+    pub key_lengths: KeyLengths,
+    pub board_creation: BoardCreation,
+}
+
+impl Config {
+    /// Loads the configuration from a TOML file.
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("when reading the config file {}", path.display()))?;
+        toml::from_str(&contents)
+            .with_context(|| format!("when parsing the config file {}", path.display()))
+    }
+
+    /// Checks that the values make sense together.
+    pub fn validate(&self) -> Result<()> {
+        if self.server.pool_size == 0 {
+            bail!("server.pool_size must be at least 1");
+        }
+        for origin in &self.server.cors_origins {
+            if origin != "*" && HeaderValue::from_str(origin).is_err() {
+                bail!(
+                    "server.cors_origins contains an invalid origin: {:?}",
+                    origin
+                );
+            }
+        }
+
+        if self.names.max_length == 0 {
+            bail!("names.max_length must be at least 1");
+        }
+        if self.names.min_length > self.names.max_length {
+            bail!(
+                "names.min_length ({}) is greater than names.max_length ({})",
+                self.names.min_length,
+                self.names.max_length
+            );
+        }
+
+        for (name, limit) in [
+            ("player", self.rate_limits.player),
+            ("submit", self.rate_limits.submit),
+            ("read", self.rate_limits.read),
+        ] {
+            if limit == Some(0) {
+                bail!("rate_limits.{} must be at least 1", name);
+            }
+        }
+
+        let operators = &self.board_creation.operators;
+        for (i, operator) in operators.iter().enumerate() {
+            if operator.name.is_empty() || operator.name.len() > MAX_OPERATOR_NAME_LENGTH {
+                bail!(
+                    "board_creation.operators: name must be 1 to {} bytes long, got {:?}",
+                    MAX_OPERATOR_NAME_LENGTH,
+                    operator.name
+                );
+            }
+            if operator.key.len() < MIN_KEY_LENGTH {
+                bail!(
+                    "board_creation.operators: key of {} must be at least {} characters long",
+                    operator.name,
+                    MIN_KEY_LENGTH
+                );
+            }
+            for other in &operators[..i] {
+                if other.name == operator.name {
+                    bail!(
+                        "board_creation.operators: name {} is used twice",
+                        operator.name
+                    );
+                }
+                if other.key == operator.key {
+                    bail!(
+                        "board_creation.operators: {} and {} have the same key",
+                        other.name,
+                        operator.name
+                    );
+                }
+            }
+        }
+
+        let lengths = &self.key_lengths;
+        for (name, length, min_length) in [
+            ("player", lengths.player, MIN_HASHED_KEY_LENGTH),
+            ("recovery", lengths.recovery, MIN_HASHED_KEY_LENGTH),
+            ("read", lengths.read, MIN_HASHED_KEY_LENGTH),
+            ("submit", lengths.submit, MIN_HASHED_KEY_LENGTH),
+            ("admin", lengths.admin, MIN_HASHED_KEY_LENGTH),
+            ("signing", lengths.signing, MIN_KEY_LENGTH),
+        ] {
+            if !(min_length..=MAX_KEY_LENGTH).contains(&length) {
+                bail!(
+                    "key_lengths.{} must be between {} and {}, got {}",
+                    name,
+                    min_length,
+                    MAX_KEY_LENGTH,
+                    length
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Format of the log output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Multi-line human-readable output.
+    #[default]
+    Pretty,
+    /// Single-line human-readable output.
+    Compact,
+    /// A json object per line.
+    Json,
+}
+
+/// How the server is reached and where it keeps its data.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub address: IpAddr,
+    pub port: u16,
+    /// Falls back to the `DATABASE_URL` environment variable.
+    pub database_url: Option<String>,
+    /// Maximum number of database connections.
+    pub pool_size: u32,
+    /// Origins allowed to make cross-origin requests, `*` allows any.
+    pub cors_origins: Vec<String>,
+    /// Addresses of reverse proxies trusted to report the address of the client
+    /// in the `X-Forwarded-For` header, used to tell clients apart for the rate limits.
+    pub trusted_proxies: Vec<IpAddr>,
+    pub log_format: LogFormat,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            address: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            port: 8000,
+            database_url: None,
+            pool_size: 10,
+            cors_origins: vec!["*".to_owned()],
+            trusted_proxies: Vec::new(),
+            log_format: LogFormat::default(),
+        }
+    }
+}
+
+/// Rules that player names have to follow.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NameRules {
+    /// Minimum length of a player name in characters.
+    pub min_length: usize,
+    /// Maximum length of a player name in characters.
+    pub max_length: usize,
+    /// Characters allowed in player names in addition to letters and digits.
+    pub allowed_symbols: String,
+    /// Whether leading and trailing whitespace is removed from player names.
+    pub trim: bool,
+    /// Whether player names have to be unique, ignoring case.
+    pub unique: bool,
+}
+
+impl Default for NameRules {
+    fn default() -> Self {
+        Self {
+            min_length: 1,
+            max_length: 32,
+            allowed_symbols: " _-.".to_owned(),
+            trim: true,
+            unique: false,
+        }
+    }
+}
+
+impl NameRules {
+    /// Checks the name against the rules, except for uniqueness.
+    /// Returns the normalized name, or the reason it is invalid.
+    pub fn validate(&self, name: &str) -> Result<String, String> {
+        let name = if self.trim { name.trim() } else { name };
+
+        let length = name.chars().count();
+        if length < self.min_length {
+            return Err(format!(
+                "name must be at least {} characters long",
+                self.min_length
+            ));
+        }
+        if length > self.max_length {
+            return Err(format!(
+                "name must be at most {} characters long",
+                self.max_length
+            ));
+        }
+
+        if let Some(c) = name
+            .chars()
+            .find(|&c| !c.is_alphanumeric() && !self.allowed_symbols.contains(c))
+        {
+            return Err(format!("name contains a forbidden character: {:?}", c));
+        }
+
+        Ok(name.to_owned())
+    }
+}
+
+/// Filter for player names and score info.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FilterConfig {
+    /// File with blocked words, one per line.
+    /// Nothing is filtered if not set.
+    pub wordlist: Option<PathBuf>,
+    /// What to do with text that contains blocked words.
+    pub mode: FilterMode,
+}
+
+/// Maximum number of requests per minute from a single client.
+/// A client is identified separately by its address and, once authorized,
+/// by its board api key and its player id.
+/// Requests are not limited if not set.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimits {
+    /// Limit for player requests: creation, key regeneration, recovery and renaming.
+    pub player: Option<u32>,
+    /// Limit for score submissions.
+    pub submit: Option<u32>,
+    /// Limit for reading scores and other information.
+    pub read: Option<u32>,
+}
+
+/// Length of the generated keys and secrets in characters.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KeyLengths {
+    pub player: usize,
+    pub recovery: usize,
+    pub read: usize,
+    pub submit: usize,
+    pub admin: usize,
+    pub signing: usize,
+}
+
+impl Default for KeyLengths {
+    fn default() -> Self {
+        Self {
+            player: 32,
+            recovery: 32,
+            read: 32,
+            submit: 32,
+            admin: 32,
+            signing: 32,
+        }
+    }
+}
+
+impl KeyLengths {
+    /// Length of the board keys with the given authority.
+    pub fn board_key(&self, authority: KeyAuthority) -> usize {
+        match authority {
+            KeyAuthority::Read => self.read,
+            KeyAuthority::Submit => self.submit,
+            KeyAuthority::Admin => self.admin,
+        }
+    }
+}
+
+/// Who is allowed to create boards over http.
+/// Boards can always be created with the admin commands.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BoardCreation {
+    /// Allow anyone to create boards without an operator key.
+    /// Meant for local development only.
+    pub open: bool,
+    pub operators: Vec<Operator>,
+}
+
+impl BoardCreation {
+    /// Finds the operator with the key.
+    /// Every key is compared, so that the time taken does not tell which one matched.
+    pub fn find_operator(&self, key: &str) -> Option<&Operator> {
+        self.operators.iter().fold(None, |found, operator| {
+            let matches = bool::from(operator.key.as_bytes().ct_eq(key.as_bytes()));
+            found.or(matches.then_some(operator))
+        })
+    }
+}
+
+/// Holder of a key that allows creating boards.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Operator {
+    /// Recorded as the creator of the boards.
+    pub name: String,
+    pub key: String,
+    /// Maximum number of boards the operator can create, unlimited if not set.
+    #[serde(default)]
+    pub max_boards: Option<u32>,
+}
+
+/// Command line options that override the config file.
+#[derive(Debug, Clone, Default, clap::Args)]
+pub struct ConfigArgs {
+    /// Port to listen on [default: 8000].
+    #[arg(long)]
+    pub port: Option<u16>,
+    /// Address to listen on [default: 0.0.0.0].
+    #[arg(long)]
+    pub address: Option<IpAddr>,
+    /// Url of the database.
+    #[arg(long, env = "DATABASE_URL")]
+    pub database_url: Option<String>,
+    /// Maximum number of database connections [default: 10].
+    #[arg(long)]
+    pub pool_size: Option<u32>,
+    /// Origin allowed to make cross-origin requests, can be repeated.
+    /// `*` allows any origin [default: *].
+    #[arg(long = "cors-origin")]
+    pub cors_origins: Vec<String>,
+    /// Address of a reverse proxy trusted to report the client address, can be repeated.
+    #[arg(long = "trusted-proxy")]
+    pub trusted_proxies: Vec<IpAddr>,
+    /// Format of the log output [default: pretty].
+    #[arg(long, value_enum)]
+    pub log_format: Option<LogFormat>,
+
+    /// Minimum length of a player name in characters [default: 1].
+    #[arg(long = "name-min-length")]
+    pub name_min_length: Option<usize>,
+    /// Maximum length of a player name in characters [default: 32].
+    #[arg(long = "name-max-length")]
+    pub name_max_length: Option<usize>,
+    /// Characters allowed in player names in addition to letters and digits [default: " _-."].
+    #[arg(long = "name-symbols")]
+    pub name_symbols: Option<String>,
+    /// Whether leading and trailing whitespace is removed from player names [default: true].
+    #[arg(long = "name-trim")]
+    pub name_trim: Option<bool>,
+    /// Require player names to be unique, ignoring case,
+    /// `--unique-names=false` turns it off [default: false].
+    #[arg(long = "unique-names", num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    pub unique_names: Option<bool>,
+
+    /// File with blocked words, one per line.
+    #[arg(long)]
+    pub wordlist: Option<PathBuf>,
+    /// What to do with text that contains blocked words [default: reject].
+    #[arg(long, value_enum)]
+    pub filter_mode: Option<FilterMode>,
+
+    /// Player requests per minute from a single client.
+    #[arg(long)]
+    pub rate_limit_player: Option<u32>,
+    /// Score submissions per minute from a single client.
+    #[arg(long)]
+    pub rate_limit_submit: Option<u32>,
+    /// Read requests per minute from a single client.
+    #[arg(long)]
+    pub rate_limit_read: Option<u32>,
+
+    /// Length of player keys [default: 32].
+    #[arg(long)]
+    pub player_key_length: Option<usize>,
+    /// Length of player recovery codes [default: 32].
+    #[arg(long)]
+    pub recovery_code_length: Option<usize>,
+    /// Length of board read keys [default: 32].
+    #[arg(long)]
+    pub read_key_length: Option<usize>,
+    /// Length of board submit keys [default: 32].
+    #[arg(long)]
+    pub submit_key_length: Option<usize>,
+    /// Length of board admin keys [default: 32].
+    #[arg(long)]
+    pub admin_key_length: Option<usize>,
+    /// Length of board signing secrets [default: 32].
+    #[arg(long)]
+    pub signing_secret_length: Option<usize>,
+
+    /// Operator key required to create boards, in addition to the ones in the config file.
+    #[arg(long, env = "NERTBOARD_OPERATOR_KEY", hide_env_values = true)]
+    pub operator_key: Option<String>,
+    /// Allow anyone to create boards without an operator key, for local development,
+    /// `--open-board-creation=false` turns it off [default: false].
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    pub open_board_creation: Option<bool>,
+}
+
+impl ConfigArgs {
+    /// Replaces the values in the config with the ones set on the command line.
+    pub fn apply(self, config: &mut Config) {
+        fn set<T>(target: &mut T, value: Option<T>) {
+            if let Some(value) = value {
+                *target = value;
+            }
+        }
+
+        let server = &mut config.server;
+        set(&mut server.port, self.port);
+        set(&mut server.address, self.address);
+        if self.database_url.is_some() {
+            server.database_url = self.database_url;
+        }
+        set(&mut server.pool_size, self.pool_size);
+        if !self.cors_origins.is_empty() {
+            server.cors_origins = self.cors_origins;
+        }
+        if !self.trusted_proxies.is_empty() {
+            server.trusted_proxies = self.trusted_proxies;
+        }
+        set(&mut server.log_format, self.log_format);
+
+        let names = &mut config.names;
+        set(&mut names.min_length, self.name_min_length);
+        set(&mut names.max_length, self.name_max_length);
+        set(&mut names.allowed_symbols, self.name_symbols);
+        set(&mut names.trim, self.name_trim);
+        set(&mut names.unique, self.unique_names);
+
+        if self.wordlist.is_some() {
+            config.filter.wordlist = self.wordlist;
+        }
+        set(&mut config.filter.mode, self.filter_mode);
+
+        let limits = &mut config.rate_limits;
+        limits.player = self.rate_limit_player.or(limits.player);
+        limits.submit = self.rate_limit_submit.or(limits.submit);
+        limits.read = self.rate_limit_read.or(limits.read);
+
+        let lengths = &mut config.key_lengths;
+        set(&mut lengths.player, self.player_key_length);
+        set(&mut lengths.recovery, self.recovery_code_length);
+        set(&mut lengths.read, self.read_key_length);
+        set(&mut lengths.submit, self.submit_key_length);
+        set(&mut lengths.admin, self.admin_key_length);
+        set(&mut lengths.signing, self.signing_secret_length);
+
+        let creation = &mut config.board_creation;
+        if let Some(key) = self.operator_key {
+            creation.operators.push(Operator {
+                name: CLI_OPERATOR_NAME.to_owned(),
+                key,
+                max_boards: None,
+            });
+        }
+        set(&mut creation.open, self.open_board_creation);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_example_config() -> Result<()> {
+        let config: Config = toml::from_str(include_str!("../config.example.toml"))?;
+        config.validate()?;
+        assert_eq!(config.server.port, 8000);
+        assert_eq!(config.filter.mode, FilterMode::Reject);
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_errors() {
+        let parse = |text: &str| toml::from_str::<Config>(text);
+        assert!(parse("[names]\nmax_lenght = 10").is_err());
+        assert!(parse("[server]\nport = \"http\"").is_err());
+
+        let invalid = |text: &str| parse(text).unwrap().validate().is_err();
+        assert!(!invalid(""));
+        assert!(invalid("[names]\nmin_length = 10\nmax_length = 5"));
+        assert!(invalid("[rate_limits]\nsubmit = 0"));
+        assert!(invalid("[key_lengths]\nadmin = 4"));
+        assert!(invalid("[key_lengths]\nplayer = 10"));
+        assert!(invalid("[server]\npool_size = 0"));
+        assert!(invalid(
+            "[[board_creation.operators]]\nname = \"ci\"\nkey = \"short\""
+        ));
+        assert!(invalid(
+            "
+[[board_creation.operators]]
+name = \"ci\"
+key = \"operator-key\"
+
+[[board_creation.operators]]
+name = \"ci\"
+key = \"another-key\"
+            "
+        ));
+        assert!(invalid("[server]\ncors_origins = [\"bad\\norigin\"]"));
+    }
+
+    #[test]
+    fn test_config_args() {
+        let mut config: Config = toml::from_str(
+            "
+[server]
+port = 9000
+pool_size = 4
+
+[rate_limits]
+read = 100
+            ",
+        )
+        .unwrap();
+        let args = ConfigArgs {
+            port: Some(9001),
+            rate_limit_submit: Some(10),
+            unique_names: Some(true),
+            ..Default::default()
+        };
+        args.apply(&mut config);
+
+        assert_eq!(config.server.port, 9001);
+        assert_eq!(config.server.pool_size, 4);
+        assert_eq!(config.rate_limits.read, Some(100));
+        assert_eq!(config.rate_limits.submit, Some(10));
+        assert!(config.names.unique);
+
+        // Flags set in the config file can be turned off
+        let args = ConfigArgs {
+            unique_names: Some(false),
+            ..Default::default()
+        };
+        args.apply(&mut config);
+        assert!(!config.names.unique);
+    }
+
+    #[test]
+    fn test_config_flags() {
+        #[derive(clap::Parser)]
+        struct Opts {
+            #[command(flatten)]
+            args: ConfigArgs,
+        }
+        let parse = |args: &[&str]| {
+            <Opts as clap::Parser>::try_parse_from(
+                std::iter::once("nertboard-server").chain(args.iter().copied()),
+            )
+            .map(|opts| opts.args)
+        };
+
+        let args = parse(&[
+            "--port",
+            "9000",
+            "--unique-names",
+            "--open-board-creation=false",
+        ])
+        .unwrap();
+        assert_eq!(args.port, Some(9000));
+        assert_eq!(args.unique_names, Some(true));
+        assert_eq!(args.open_board_creation, Some(false));
+
+        let args = parse(&[]).unwrap();
+        assert_eq!(args.unique_names, None);
+        assert_eq!(args.open_board_creation, None);
+
+        assert!(parse(&["9000"]).is_err());
+    }
+}
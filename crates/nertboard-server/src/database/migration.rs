@@ -0,0 +1,589 @@
+use super::*;
+
+use crate::api_key::HashedKey;
+
+use std::{future::Future, pin::Pin};
+
+use chrono::Utc;
+use color_eyre::eyre::{bail, Context};
+use sqlx::AnyConnection;
+
+type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Migration step that cannot be expressed in plain sql.
+type MigrationFn =
+    for<'c> fn(&'c mut AnyConnection, Dialect) -> BoxFuture<'c, color_eyre::Result<()>>;
+
+/// A step in the evolution of the database schema.
+pub(super) struct Migration {
+    version: i64,
+    description: &'static str,
+    /// Statements executed in order inside a single transaction.
+    /// `{serial}` is replaced with the dialect-specific autoincrementing primary key.
+    pub(super) statements: &'static [&'static str],
+    /// Executed after the statements inside the same transaction.
+    /// Has to be a single step, so that it is either applied fully or not at all.
+    pub(super) code: Option<MigrationFn>,
+}
+
+/// All migrations, ordered by version.
+/// Never edit a migration that has been released, add a new one instead.
+pub(super) const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "initial schema",
+        statements: &[
+            "
+CREATE TABLE IF NOT EXISTS boards
+(
+    board_id {serial},
+    board_name TEXT NOT NULL,
+    read_key TEXT,
+    submit_key TEXT,
+    admin_key TEXT NOT NULL
+)
+            ",
+            "
+CREATE TABLE IF NOT EXISTS players
+(
+    player_id {serial},
+    player_key TEXT NOT NULL,
+    name TEXT NOT NULL
+)
+            ",
+            "
+CREATE TABLE IF NOT EXISTS scores
+(
+    board_id INTEGER NOT NULL,
+    player_id INTEGER NOT NULL,
+    score INTEGER NOT NULL,
+    extra_info TEXT,
+    FOREIGN KEY(board_id) REFERENCES boards(board_id),
+    FOREIGN KEY(player_id) REFERENCES players(player_id)
+)
+            ",
+        ],
+        code: Some(|connection, dialect| Box::pin(rename_legacy_key(connection, dialect))),
+    },
+    Migration {
+        version: 2,
+        description: "board settings, score ids and submission times",
+        statements: &[
+            "ALTER TABLE boards ADD COLUMN score_order VARCHAR(16) NOT NULL DEFAULT 'descending'",
+            "ALTER TABLE boards ADD COLUMN score_policy VARCHAR(16) NOT NULL DEFAULT 'all'",
+            "
+CREATE TABLE scores_v2
+(
+    score_id {serial},
+    board_id INTEGER NOT NULL,
+    player_id INTEGER NOT NULL,
+    score INTEGER NOT NULL,
+    extra_info TEXT,
+    submitted_at BIGINT NOT NULL,
+    FOREIGN KEY(board_id) REFERENCES boards(board_id),
+    FOREIGN KEY(player_id) REFERENCES players(player_id)
+)
+            ",
+            "
+INSERT INTO scores_v2 (board_id, player_id, score, extra_info, submitted_at)
+SELECT board_id, player_id, score, extra_info, 0 FROM scores
+            ",
+            "DROP TABLE scores",
+            "ALTER TABLE scores_v2 RENAME TO scores",
+        ],
+        code: None,
+    },
+    Migration {
+        version: 3,
+        description: "hash board and player keys",
+        statements: &[],
+        code: Some(|connection, dialect| Box::pin(hash_keys(connection, dialect))),
+    },
+    Migration {
+        version: 4,
+        description: "multiple keys per board",
+        statements: &[
+            "
+CREATE TABLE board_keys
+(
+    key_id {serial},
+    board_id INTEGER NOT NULL,
+    label TEXT NOT NULL,
+    authority VARCHAR(16) NOT NULL,
+    key_hash TEXT NOT NULL,
+    created_at BIGINT NOT NULL,
+    expires_at BIGINT,
+    last_used_at BIGINT,
+    FOREIGN KEY(board_id) REFERENCES boards(board_id)
+)
+            ",
+            "
+INSERT INTO board_keys (board_id, label, authority, key_hash, created_at)
+SELECT board_id, 'read', 'read', read_key, 0 FROM boards WHERE read_key IS NOT NULL
+            ",
+            "
+INSERT INTO board_keys (board_id, label, authority, key_hash, created_at)
+SELECT board_id, 'submit', 'submit', submit_key, 0 FROM boards WHERE submit_key IS NOT NULL
+            ",
+            "
+INSERT INTO board_keys (board_id, label, authority, key_hash, created_at)
+SELECT board_id, 'admin', 'admin', admin_key, 0 FROM boards
+            ",
+            "ALTER TABLE boards DROP COLUMN read_key",
+            "ALTER TABLE boards DROP COLUMN submit_key",
+            "ALTER TABLE boards DROP COLUMN admin_key",
+        ],
+        code: None,
+    },
+    Migration {
+        version: 5,
+        description: "public read boards",
+        statements: &["ALTER TABLE boards ADD COLUMN public_read INTEGER NOT NULL DEFAULT 0"],
+        code: None,
+    },
+    Migration {
+        version: 6,
+        description: "player recovery codes",
+        statements: &["ALTER TABLE players ADD COLUMN recovery_hash TEXT"],
+        code: None,
+    },
+    Migration {
+        version: 7,
+        description: "player name history",
+        statements: &["
+CREATE TABLE player_names
+(
+    rename_id {serial},
+    player_id INTEGER NOT NULL,
+    old_name TEXT NOT NULL,
+    new_name TEXT NOT NULL,
+    renamed_at BIGINT NOT NULL,
+    FOREIGN KEY(player_id) REFERENCES players(player_id)
+)
+            "],
+        code: None,
+    },
+    Migration {
+        version: 8,
+        description: "flag content for moderation",
+        statements: &[
+            "ALTER TABLE players ADD COLUMN flagged INTEGER NOT NULL DEFAULT 0",
+            "ALTER TABLE scores ADD COLUMN flagged INTEGER NOT NULL DEFAULT 0",
+        ],
+        code: None,
+    },
+    Migration {
+        version: 9,
+        description: "score moderation and bans",
+        statements: &[
+            "ALTER TABLE scores ADD COLUMN hidden INTEGER NOT NULL DEFAULT 0",
+            "
+CREATE TABLE board_bans
+(
+    board_id INTEGER NOT NULL,
+    player_id INTEGER NOT NULL,
+    banned_at BIGINT NOT NULL,
+    PRIMARY KEY(board_id, player_id),
+    FOREIGN KEY(board_id) REFERENCES boards(board_id),
+    FOREIGN KEY(player_id) REFERENCES players(player_id)
+)
+            ",
+        ],
+        code: None,
+    },
+    Migration {
+        version: 10,
+        description: "score validation rules",
+        statements: &[
+            "ALTER TABLE boards ADD COLUMN min_score INTEGER",
+            "ALTER TABLE boards ADD COLUMN max_score INTEGER",
+            "ALTER TABLE boards ADD COLUMN submission_count INTEGER",
+            "ALTER TABLE boards ADD COLUMN submission_window INTEGER",
+            "ALTER TABLE boards ADD COLUMN max_info_length INTEGER",
+            "
+CREATE TABLE submissions
+(
+    board_id INTEGER NOT NULL,
+    player_id INTEGER NOT NULL,
+    submitted_at BIGINT NOT NULL,
+    FOREIGN KEY(board_id) REFERENCES boards(board_id),
+    FOREIGN KEY(player_id) REFERENCES players(player_id)
+)
+            ",
+        ],
+        code: None,
+    },
+    Migration {
+        version: 11,
+        description: "signed submissions",
+        statements: &[
+            "ALTER TABLE boards ADD COLUMN signing_secret TEXT",
+            "
+CREATE TABLE submission_nonces
+(
+    board_id INTEGER NOT NULL,
+    nonce VARCHAR(64) NOT NULL,
+    used_at BIGINT NOT NULL,
+    PRIMARY KEY(board_id, nonce),
+    FOREIGN KEY(board_id) REFERENCES boards(board_id)
+)
+            ",
+        ],
+        code: None,
+    },
+    Migration {
+        version: 12,
+        description: "board creators",
+        statements: &["ALTER TABLE boards ADD COLUMN created_by VARCHAR(64)"],
+        code: None,
+    },
+    Migration {
+        version: 13,
+        description: "board metadata",
+        statements: &[
+            "ALTER TABLE boards ADD COLUMN display_name TEXT",
+            "ALTER TABLE boards ADD COLUMN description TEXT",
+            "ALTER TABLE boards ADD COLUMN created_at BIGINT",
+        ],
+        code: None,
+    },
+    Migration {
+        version: 14,
+        description: "index scores by player",
+        statements: &["CREATE INDEX scores_board_player ON scores (board_id, player_id)"],
+        code: None,
+    },
+    Migration {
+        version: 15,
+        description: "unique board names",
+        statements: &[],
+        code: Some(|connection, dialect| Box::pin(unique_board_names(connection, dialect))),
+    },
+    Migration {
+        version: 16,
+        description: "operators",
+        statements: &["
+CREATE TABLE operators
+(
+    operator_name VARCHAR(64) NOT NULL PRIMARY KEY
+)
+            "],
+        code: None,
+    },
+];
+
+/// Version of the schema expected by this build.
+pub const SCHEMA_VERSION: i64 = MIGRATIONS[MIGRATIONS.len() - 1].version;
+
+#[derive(Debug, Clone, Copy)]
+pub(super) enum Dialect {
+    Sqlite,
+    Postgres,
+    MySql,
+}
+
+impl Dialect {
+    fn from_backend(name: &str) -> color_eyre::Result<Self> {
+        match name {
+            "SQLite" => Ok(Self::Sqlite),
+            "PostgreSQL" => Ok(Self::Postgres),
+            "MySQL" => Ok(Self::MySql),
+            _ => bail!("unsupported database backend: {}", name),
+        }
+    }
+
+    fn serial(&self) -> &'static str {
+        match self {
+            Self::Sqlite => "INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT",
+            Self::Postgres => "SERIAL PRIMARY KEY",
+            Self::MySql => "INTEGER NOT NULL PRIMARY KEY AUTO_INCREMENT",
+        }
+    }
+
+    /// Replaces the `?` placeholders in the query with the ones of the dialect.
+    pub(super) fn placeholders(&self, query: &str) -> String {
+        match self {
+            Self::Sqlite | Self::MySql => query.to_owned(),
+            Self::Postgres => {
+                let mut index = 0;
+                query
+                    .split('?')
+                    .enumerate()
+                    .map(|(i, part)| {
+                        if i == 0 {
+                            part.to_owned()
+                        } else {
+                            index += 1;
+                            format!("${}{}", index, part)
+                        }
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Returns the version of the schema currently in the database,
+/// or 0 if no migrations have been applied yet.
+pub async fn schema_version(database: &DatabasePool) -> color_eyre::Result<i64> {
+    sqlx::query(
+        "
+CREATE TABLE IF NOT EXISTS schema_versions
+(
+    version BIGINT NOT NULL PRIMARY KEY,
+    applied_at BIGINT NOT NULL
+)
+        ",
+    )
+    .execute(database)
+    .await
+    .context("when creating table `schema_versions`")?;
+
+    // Steps of the migration in progress
+    sqlx::query(
+        "
+CREATE TABLE IF NOT EXISTS schema_steps
+(
+    version BIGINT NOT NULL,
+    step BIGINT NOT NULL,
+    PRIMARY KEY(version, step)
+)
+        ",
+    )
+    .execute(database)
+    .await
+    .context("when creating table `schema_steps`")?;
+
+    let version = sqlx::query("SELECT COALESCE(MAX(version), 0) AS version FROM schema_versions")
+        .try_map(|row: AnyRow| row.try_get::<i64, _>("version"))
+        .fetch_one(database)
+        .await
+        .context("when querying the schema version")?;
+    Ok(version)
+}
+
+/// Checks that the database schema is not newer than the one supported by this build.
+pub async fn check_schema_version(database: &DatabasePool) -> color_eyre::Result<i64> {
+    let version = schema_version(database).await?;
+    if version > SCHEMA_VERSION {
+        bail!(
+            "database schema version {} is newer than the latest supported version {}, \
+            please update the server",
+            version,
+            SCHEMA_VERSION
+        );
+    }
+    Ok(version)
+}
+
+/// Applies all migrations newer than the current schema version.
+///
+/// Each migration runs in a transaction, but MySQL commits every schema change
+/// on its own, so a failed migration might be left half-applied there.
+/// To resume it safely, every step is recorded as soon as it is applied,
+/// and recorded steps are skipped on the next run.
+pub async fn migrate(database: &DatabasePool) -> color_eyre::Result<()> {
+    let current = check_schema_version(database).await?;
+
+    let mut connection = database.acquire().await?;
+    let dialect = Dialect::from_backend(connection.backend_name())?;
+
+    for migration in MIGRATIONS
+        .iter()
+        .filter(|migration| migration.version > current)
+    {
+        info!(
+            "Applying migration {}: {}",
+            migration.version, migration.description
+        );
+
+        let mut transaction = sqlx::Connection::begin(&mut *connection).await?;
+        let applied =
+            sqlx::query(&dialect.placeholders("SELECT step FROM schema_steps WHERE version = ?"))
+                .bind(migration.version)
+                .try_map(|row: AnyRow| row.try_get::<i64, _>("step"))
+                .fetch_all(&mut *transaction)
+                .await
+                .context("when querying the applied steps")?;
+        if !applied.is_empty() {
+            info!(
+                "Resuming migration {} after {} applied steps",
+                migration.version,
+                applied.len()
+            );
+        }
+
+        // The code runs after all statements as the last step
+        let steps = migration.statements.len() + usize::from(migration.code.is_some());
+        for step in 0..steps {
+            if applied.contains(&(step as i64)) {
+                continue;
+            }
+            let context = || {
+                format!(
+                    "when applying step {} of migration {}",
+                    step, migration.version
+                )
+            };
+            if let Some(statement) = migration.statements.get(step) {
+                let statement = statement.replace("{serial}", dialect.serial());
+                sqlx::query(&statement)
+                    .execute(&mut *transaction)
+                    .await
+                    .with_context(context)?;
+            } else if let Some(code) = migration.code {
+                code(&mut transaction, dialect)
+                    .await
+                    .with_context(context)?;
+            }
+
+            sqlx::query(
+                &dialect.placeholders("INSERT INTO schema_steps (version, step) VALUES (?, ?)"),
+            )
+            .bind(migration.version)
+            .bind(step as i64)
+            .execute(&mut *transaction)
+            .await
+            .context("when recording the migration step")?;
+        }
+
+        sqlx::query(&dialect.placeholders("DELETE FROM schema_steps WHERE version = ?"))
+            .bind(migration.version)
+            .execute(&mut *transaction)
+            .await
+            .context("when clearing the migration steps")?;
+        sqlx::query(
+            &dialect
+                .placeholders("INSERT INTO schema_versions (version, applied_at) VALUES (?, ?)"),
+        )
+        .bind(migration.version)
+        .bind(Utc::now().timestamp())
+        .execute(&mut *transaction)
+        .await
+        .context("when recording the schema version")?;
+        transaction.commit().await?;
+    }
+
+    Ok(())
+}
+
+/// Renames the `key` column of players in databases created before the migrations,
+/// since `key` is a reserved word in MySQL.
+/// Such databases can only be SQLite, the only backend supported back then.
+async fn rename_legacy_key(
+    connection: &mut AnyConnection,
+    dialect: Dialect,
+) -> color_eyre::Result<()> {
+    let Dialect::Sqlite = dialect else {
+        return Ok(());
+    };
+    let legacy = sqlx::query(
+        "SELECT COUNT(*) AS columns FROM pragma_table_info('players') WHERE name = 'key'",
+    )
+    .try_map(|row: AnyRow| row.try_get::<i64, _>("columns"))
+    .fetch_one(&mut *connection)
+    .await?;
+    if legacy > 0 {
+        sqlx::query("ALTER TABLE players RENAME COLUMN key TO player_key")
+            .execute(&mut *connection)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Renames the boards that share the name with an older board and makes board names unique.
+/// Only the oldest of such boards could be found by its name before.
+async fn unique_board_names(
+    connection: &mut AnyConnection,
+    dialect: Dialect,
+) -> color_eyre::Result<()> {
+    let duplicates = sqlx::query(
+        "
+SELECT board_id, board_name FROM boards
+WHERE board_id > (
+    SELECT MIN(board_id) FROM boards AS first
+    WHERE first.board_name = boards.board_name
+)
+        ",
+    )
+    .try_map(|row: AnyRow| {
+        Ok((
+            row.try_get::<Id, _>("board_id")?,
+            row.try_get::<String, _>("board_name")?,
+        ))
+    })
+    .fetch_all(&mut *connection)
+    .await?;
+    let rename = dialect.placeholders("UPDATE boards SET board_name = ? WHERE board_id = ?");
+    for (board_id, name) in duplicates {
+        let new_name = format!("{}-{}", name, board_id);
+        info!(
+            "Renaming board {} with a duplicate name to {}",
+            name, new_name
+        );
+        sqlx::query(&rename)
+            .bind(&new_name)
+            .bind(board_id)
+            .execute(&mut *connection)
+            .await?;
+    }
+
+    // MySQL can only index a prefix of a text column
+    let index = match dialect {
+        Dialect::Sqlite | Dialect::Postgres => {
+            "CREATE UNIQUE INDEX boards_name ON boards (board_name)"
+        }
+        Dialect::MySql => "CREATE UNIQUE INDEX boards_name ON boards (board_name(255))",
+    };
+    sqlx::query(index).execute(&mut *connection).await?;
+    Ok(())
+}
+
+/// Replaces plain text keys with their hashes.
+async fn hash_keys(connection: &mut AnyConnection, dialect: Dialect) -> color_eyre::Result<()> {
+    let boards = sqlx::query("SELECT board_id, read_key, submit_key, admin_key FROM boards")
+        .try_map(|row: AnyRow| {
+            Ok((
+                row.try_get::<Id, _>("board_id")?,
+                decode_optional::<String>(&row, "read_key")?,
+                decode_optional::<String>(&row, "submit_key")?,
+                row.try_get::<String, _>("admin_key")?,
+            ))
+        })
+        .fetch_all(&mut *connection)
+        .await?;
+    let update_board = dialect.placeholders(
+        "UPDATE boards SET read_key = ?, submit_key = ?, admin_key = ? WHERE board_id = ?",
+    );
+    for (board_id, read, submit, admin) in boards {
+        let hash = |key: Option<String>| key.map(|key| HashedKey::hash(&key).as_str().to_owned());
+        sqlx::query(&update_board)
+            .bind(hash(read))
+            .bind(hash(submit))
+            .bind(hash(Some(admin)))
+            .bind(board_id)
+            .execute(&mut *connection)
+            .await?;
+    }
+
+    let players = sqlx::query("SELECT player_id, player_key FROM players")
+        .try_map(|row: AnyRow| {
+            Ok((
+                row.try_get::<Id, _>("player_id")?,
+                row.try_get::<String, _>("player_key")?,
+            ))
+        })
+        .fetch_all(&mut *connection)
+        .await?;
+    let update_player =
+        dialect.placeholders("UPDATE players SET player_key = ? WHERE player_id = ?");
+    for (player_id, key) in players {
+        sqlx::query(&update_player)
+            .bind(HashedKey::hash(&key).as_str())
+            .bind(player_id)
+            .execute(&mut *connection)
+            .await?;
+    }
+
+    Ok(())
+}
@@ -1,9 +1,19 @@
 mod init;
+mod migration;
+#[cfg(test)]
+mod tests;
 
 pub use self::init::init_database;
+pub use self::migration::{check_schema_version, SCHEMA_VERSION};
 
-use axum::http::StatusCode;
-use serde::{Deserialize, Serialize};
+use crate::prelude::*;
+
+use axum::{
+    extract::rejection::{JsonRejection, PathRejection, QueryRejection},
+    http::{header, HeaderValue, StatusCode},
+};
+use nertboard_core::{BoardSettings, ErrorCode, ErrorResponse};
+use sqlx::{any::AnyRow, Row, TypeInfo, ValueRef};
 
 pub type DatabasePool = sqlx::AnyPool; // TODO: behind a trait?
 
@@ -12,11 +22,42 @@ pub type RequestResult<T, E = RequestError> = std::result::Result<T, E>;
 pub type Id = i32;
 pub type Score = i32;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ScoreRecord {
-    pub player_id: Id,
-    pub score: Score,
-    pub extra_info: Option<String>,
+/// A board as stored in the database.
+#[derive(Debug, Clone)]
+pub struct Board {
+    pub id: Id,
+    pub settings: BoardSettings,
+}
+
+/// Decodes a text column using the `parse` function.
+pub fn decode_column<T>(
+    row: &AnyRow,
+    column: &str,
+    parse: impl FnOnce(&str) -> Option<T>,
+) -> sqlx::Result<T> {
+    let value: String = row.try_get(column)?;
+    parse(&value).ok_or_else(|| sqlx::Error::ColumnDecode {
+        index: column.to_owned(),
+        source: format!("unexpected value: {}", value).into(),
+    })
+}
+
+/// Checks whether the query failed because of a unique constraint.
+pub fn is_unique_violation(error: &sqlx::Error) -> bool {
+    matches!(error, sqlx::Error::Database(error) if error.is_unique_violation())
+}
+
+/// Decodes a nullable column, `None` if the value is null.
+/// The `Any` driver does not report null values to `Option<T>`, so it cannot be used directly.
+pub fn decode_optional<'r, T>(row: &'r AnyRow, column: &str) -> sqlx::Result<Option<T>>
+where
+    T: sqlx::Decode<'r, sqlx::Any> + sqlx::Type<sqlx::Any>,
+{
+    let value = row.try_get_raw(column)?;
+    if value.type_info().name() == "NULL" {
+        return Ok(None);
+    }
+    row.try_get(column).map(Some)
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -27,12 +68,50 @@ pub enum RequestError {
     Forbidden,
     #[error("player key is invalid")]
     InvalidPlayer,
+    #[error("invalid player name: {0}")]
+    InvalidPlayerName(String),
+    #[error("{0} contains blocked words")]
+    RejectedText(&'static str),
     #[error("invalid board name: {0}")]
     InvalidBoardName(String),
     #[error("a board called {0} already exists")]
     BoardAlreadyExists(String),
     #[error("a board called {0} not found")]
     NoSuchBoard(String),
+    #[error("player {0} has no scores on the board")]
+    NoPlayerScore(Id),
+    #[error("key {0} not found")]
+    NoSuchKey(Id),
+    #[error("score {0} not found")]
+    NoSuchScore(Id),
+    #[error("player {0} not found")]
+    NoSuchPlayer(Id),
+    #[error("player is banned from the board")]
+    PlayerBanned,
+    #[error("score rejected: {0}")]
+    ScoreRejected(String),
+    #[error("invalid board settings: {0}")]
+    InvalidSettings(&'static str),
+    #[error("invalid submission signature: {0}")]
+    InvalidSignature(&'static str),
+    #[error("cannot remove the only admin key of the board")]
+    LastAdminKey,
+    #[error("too many requests, retry in {0} seconds")]
+    TooManyRequests(u64),
+    #[error("the quota of {0} boards has been reached")]
+    QuotaExceeded(u32),
+    #[error("{0}")]
+    InvalidHeader(&'static str),
+    #[error("invalid request body")]
+    InvalidBody(#[from] JsonRejection),
+    #[error("invalid query parameters")]
+    InvalidQuery(#[from] QueryRejection),
+    #[error("invalid path parameters")]
+    InvalidPath(#[from] PathRejection),
+    #[error("no route found")]
+    RouteNotFound,
+    #[error("method not allowed")]
+    MethodNotAllowed,
     #[error("database error: {0}")]
     Sql(#[from] sqlx::Error),
 }
@@ -43,17 +122,89 @@ impl RequestError {
             RequestError::Unathorized => StatusCode::UNAUTHORIZED,
             RequestError::Forbidden => StatusCode::FORBIDDEN,
             RequestError::InvalidPlayer => StatusCode::FORBIDDEN,
+            RequestError::InvalidPlayerName(_) => StatusCode::BAD_REQUEST,
+            RequestError::RejectedText(_) => StatusCode::BAD_REQUEST,
             RequestError::InvalidBoardName(_) => StatusCode::BAD_REQUEST,
             RequestError::BoardAlreadyExists(_) => StatusCode::CONFLICT,
             RequestError::NoSuchBoard(_) => StatusCode::NOT_FOUND,
+            RequestError::NoPlayerScore(_) => StatusCode::NOT_FOUND,
+            RequestError::NoSuchKey(_) => StatusCode::NOT_FOUND,
+            RequestError::NoSuchScore(_) => StatusCode::NOT_FOUND,
+            RequestError::NoSuchPlayer(_) => StatusCode::NOT_FOUND,
+            RequestError::PlayerBanned => StatusCode::FORBIDDEN,
+            RequestError::ScoreRejected(_) => StatusCode::BAD_REQUEST,
+            RequestError::InvalidSettings(_) => StatusCode::BAD_REQUEST,
+            RequestError::InvalidSignature(_) => StatusCode::FORBIDDEN,
+            RequestError::LastAdminKey => StatusCode::CONFLICT,
+            RequestError::TooManyRequests(_) => StatusCode::TOO_MANY_REQUESTS,
+            RequestError::QuotaExceeded(_) => StatusCode::FORBIDDEN,
+            RequestError::InvalidHeader(_) => StatusCode::BAD_REQUEST,
+            RequestError::InvalidBody(rejection) => rejection.status(),
+            RequestError::InvalidQuery(rejection) => rejection.status(),
+            RequestError::InvalidPath(rejection) => rejection.status(),
+            RequestError::RouteNotFound => StatusCode::NOT_FOUND,
+            RequestError::MethodNotAllowed => StatusCode::METHOD_NOT_ALLOWED,
             RequestError::Sql(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
+
+    fn code(&self) -> ErrorCode {
+        match self {
+            RequestError::Unathorized => ErrorCode::Unauthorized,
+            RequestError::Forbidden => ErrorCode::Forbidden,
+            RequestError::InvalidPlayer => ErrorCode::InvalidPlayer,
+            RequestError::InvalidPlayerName(_) => ErrorCode::InvalidPlayerName,
+            RequestError::RejectedText(_) => ErrorCode::RejectedText,
+            RequestError::InvalidBoardName(_) => ErrorCode::InvalidBoardName,
+            RequestError::BoardAlreadyExists(_) => ErrorCode::BoardAlreadyExists,
+            RequestError::NoSuchBoard(_) => ErrorCode::NoSuchBoard,
+            RequestError::NoPlayerScore(_) => ErrorCode::NoPlayerScore,
+            RequestError::NoSuchKey(_) => ErrorCode::NoSuchKey,
+            RequestError::NoSuchScore(_) => ErrorCode::NoSuchScore,
+            RequestError::NoSuchPlayer(_) => ErrorCode::NoSuchPlayer,
+            RequestError::PlayerBanned => ErrorCode::PlayerBanned,
+            RequestError::ScoreRejected(_) => ErrorCode::ScoreRejected,
+            RequestError::InvalidSettings(_) => ErrorCode::InvalidSettings,
+            RequestError::InvalidSignature(_) => ErrorCode::InvalidSignature,
+            RequestError::LastAdminKey => ErrorCode::LastAdminKey,
+            RequestError::TooManyRequests(_) => ErrorCode::TooManyRequests,
+            RequestError::QuotaExceeded(_) => ErrorCode::QuotaExceeded,
+            RequestError::InvalidHeader(_)
+            | RequestError::InvalidBody(_)
+            | RequestError::InvalidQuery(_)
+            | RequestError::InvalidPath(_) => ErrorCode::InvalidRequest,
+            RequestError::RouteNotFound => ErrorCode::NotFound,
+            RequestError::MethodNotAllowed => ErrorCode::MethodNotAllowed,
+            RequestError::Sql(_) => ErrorCode::Internal,
+        }
+    }
+
+    fn details(&self) -> Option<String> {
+        match self {
+            RequestError::InvalidBody(rejection) => Some(rejection.body_text()),
+            RequestError::InvalidQuery(rejection) => Some(rejection.body_text()),
+            RequestError::InvalidPath(rejection) => Some(rejection.body_text()),
+            _ => None,
+        }
+    }
 }
 
 impl axum::response::IntoResponse for RequestError {
     fn into_response(self) -> axum::response::Response {
-        let body = format!("{}", self);
-        (self.status(), body).into_response()
+        if let RequestError::Sql(err) = &self {
+            error!("{}", err);
+        }
+        let body = ErrorResponse {
+            code: self.code(),
+            message: self.to_string(),
+            details: self.details(),
+        };
+        let mut response = (self.status(), axum::Json(body)).into_response();
+        if let RequestError::TooManyRequests(retry_after) = self {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+        }
+        response
     }
 }
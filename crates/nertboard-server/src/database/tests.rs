@@ -0,0 +1,197 @@
+use super::*;
+
+use crate::api_key::HashedKey;
+
+use color_eyre::Result;
+use rand::Rng;
+
+/// Server to run the migration tests against instead of SQLite,
+/// e.g. `mysql://root@localhost` or `postgres://postgres@localhost`.
+/// Every test creates a database of its own on it, which is not dropped afterwards.
+const TEST_SERVER_VAR: &str = "NERTBOARD_TEST_DATABASE_URL";
+
+async fn empty_sqlite() -> Result<DatabasePool> {
+    crate::setup::setup_test();
+    let pool = sqlx::any::AnyPoolOptions::new()
+        .min_connections(1)
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .context("when connecting to the in-memory database")?;
+    Ok(pool)
+}
+
+/// Connects to an empty database on the server from [`TEST_SERVER_VAR`],
+/// or to an in-memory SQLite database if it is not set.
+async fn empty_database() -> Result<DatabasePool> {
+    let Ok(server) = std::env::var(TEST_SERVER_VAR) else {
+        return empty_sqlite().await;
+    };
+    crate::setup::setup_test();
+
+    let name = format!("nertboard_test_{}", rand::thread_rng().gen::<u32>());
+    let admin = sqlx::any::AnyPoolOptions::new()
+        .max_connections(1)
+        .connect(&server)
+        .await
+        .context("when connecting to the test database server")?;
+    sqlx::query(&format!("CREATE DATABASE {}", name))
+        .execute(&admin)
+        .await
+        .context("when creating a test database")?;
+
+    let pool = sqlx::any::AnyPoolOptions::new()
+        .max_connections(1)
+        .connect(&format!("{}/{}", server.trim_end_matches('/'), name))
+        .await
+        .context("when connecting to the test database")?;
+    Ok(pool)
+}
+
+#[tokio::test]
+async fn test_migrate_fresh() -> Result<()> {
+    let database = empty_database().await?;
+
+    init_database(&database).await?;
+    assert_eq!(check_schema_version(&database).await?, SCHEMA_VERSION);
+
+    // Migrating again does nothing
+    init_database(&database).await?;
+    assert_eq!(check_schema_version(&database).await?, SCHEMA_VERSION);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_migrate_resume() -> Result<()> {
+    let database = empty_database().await?;
+    init_database(&database).await?;
+
+    // Pretend the last migration failed after applying all of its steps,
+    // which can only happen on MySQL
+    let version = SCHEMA_VERSION;
+    sqlx::query(&format!(
+        "DELETE FROM schema_versions WHERE version = {}",
+        version
+    ))
+    .execute(&database)
+    .await?;
+    let last = &migration::MIGRATIONS[migration::MIGRATIONS.len() - 1];
+    let steps = last.statements.len() + usize::from(last.code.is_some());
+    for step in 0..steps {
+        sqlx::query(&format!(
+            "INSERT INTO schema_steps (version, step) VALUES ({}, {})",
+            version, step
+        ))
+        .execute(&database)
+        .await?;
+    }
+    assert_eq!(check_schema_version(&database).await?, version - 1);
+
+    // Applied statements are not executed again
+    init_database(&database).await?;
+    assert_eq!(check_schema_version(&database).await?, SCHEMA_VERSION);
+
+    let remaining = sqlx::query("SELECT COUNT(*) AS steps FROM schema_steps")
+        .try_map(|row: AnyRow| row.try_get::<i64, _>("steps"))
+        .fetch_one(&database)
+        .await?;
+    assert_eq!(remaining, 0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_migrate_legacy() -> Result<()> {
+    // Schema created before migrations were introduced, which only supported SQLite
+    let database = empty_sqlite().await?;
+
+    for statement in [
+        "CREATE TABLE boards (board_id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT, board_name TEXT NOT NULL, read_key TEXT, submit_key TEXT, admin_key TEXT NOT NULL)",
+        "CREATE TABLE players (player_id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT, key TEXT NOT NULL, name TEXT NOT NULL)",
+        "CREATE TABLE scores (board_id INTEGER NOT NULL, player_id INTEGER NOT NULL, score INTEGER NOT NULL, extra_info TEXT)",
+        "INSERT INTO boards (board_name, read_key, submit_key, admin_key) VALUES ('board', 'read', 'submit', 'admin')",
+        "INSERT INTO boards (board_name, read_key, submit_key, admin_key) VALUES ('board', NULL, NULL, 'other')",
+        "INSERT INTO players (key, name) VALUES ('key', 'nertsal')",
+        "INSERT INTO scores (board_id, player_id, score) VALUES (1, 1, 10)",
+    ] {
+        sqlx::query(statement).execute(&database).await?;
+    }
+
+    init_database(&database).await?;
+    assert_eq!(check_schema_version(&database).await?, SCHEMA_VERSION);
+
+    let (score_id, score, submitted_at) =
+        sqlx::query("SELECT score_id, score, submitted_at FROM scores")
+            .try_map(|row: AnyRow| {
+                Ok((
+                    row.try_get::<Id, _>("score_id")?,
+                    row.try_get::<Score, _>("score")?,
+                    row.try_get::<i64, _>("submitted_at")?,
+                ))
+            })
+            .fetch_one(&database)
+            .await?;
+    assert_eq!((score_id, score, submitted_at), (1, 10, 0));
+
+    let order = sqlx::query("SELECT score_order FROM boards")
+        .try_map(|row: AnyRow| row.try_get::<String, _>("score_order"))
+        .fetch_one(&database)
+        .await?;
+    assert_eq!(order, "descending");
+
+    // Keys are hashed
+    let read_key = sqlx::query("SELECT key_hash FROM board_keys WHERE authority = 'read'")
+        .try_map(|row: AnyRow| row.try_get::<String, _>("key_hash"))
+        .fetch_one(&database)
+        .await?;
+    assert_ne!(read_key, "read");
+    assert!(HashedKey::from_stored(read_key).verify("read"));
+
+    // The reserved column name is replaced
+    let player_key = sqlx::query("SELECT player_key FROM players")
+        .try_map(|row: AnyRow| row.try_get::<String, _>("player_key"))
+        .fetch_one(&database)
+        .await?;
+    assert!(HashedKey::from_stored(player_key).verify("key"));
+
+    // Boards with the same name are renamed
+    let names = sqlx::query("SELECT board_name FROM boards ORDER BY board_id")
+        .try_map(|row: AnyRow| row.try_get::<String, _>("board_name"))
+        .fetch_all(&database)
+        .await?;
+    assert_eq!(names, ["board", "board-2"]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_newer_schema() -> Result<()> {
+    let database = empty_database().await?;
+    init_database(&database).await?;
+
+    sqlx::query(&format!(
+        "INSERT INTO schema_versions (version, applied_at) VALUES ({}, 0)",
+        SCHEMA_VERSION + 1
+    ))
+    .execute(&database)
+    .await?;
+
+    assert!(check_schema_version(&database).await.is_err());
+    assert!(init_database(&database).await.is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_placeholders() {
+    use migration::Dialect;
+
+    let query = "UPDATE players SET player_key = ? WHERE player_id = ?";
+    assert_eq!(Dialect::Sqlite.placeholders(query), query);
+    assert_eq!(Dialect::MySql.placeholders(query), query);
+    assert_eq!(
+        Dialect::Postgres.placeholders(query),
+        "UPDATE players SET player_key = $1 WHERE player_id = $2"
+    );
+}
@@ -0,0 +1,146 @@
+use crate::{
+    config::FilterConfig,
+    database::{RequestError, RequestResult},
+    prelude::*,
+};
+
+use std::ops::Range;
+
+/// Finds unwanted words in user-provided text.
+pub trait TextFilter: Send + Sync {
+    /// Returns the ranges of the matches, in characters.
+    fn find(&self, text: &str) -> Vec<Range<usize>>;
+}
+
+/// What happens to text that contains unwanted words.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum FilterMode {
+    /// Refuse the request.
+    #[default]
+    Reject,
+    /// Replace the matches with asterisks.
+    Mask,
+    /// Accept the text as is and flag it for moderation.
+    Flag,
+}
+
+/// Case-insensitive list of blocked words.
+#[derive(Debug, Clone, Default)]
+pub struct Wordlist {
+    words: Vec<Vec<char>>,
+}
+
+impl Wordlist {
+    pub fn new<S: AsRef<str>>(words: impl IntoIterator<Item = S>) -> Self {
+        Self {
+            words: words
+                .into_iter()
+                .map(|word| lowercase(word.as_ref()))
+                .filter(|word| !word.is_empty())
+                .collect(),
+        }
+    }
+
+    /// Loads a wordlist file with a single word per line.
+    /// Empty lines and lines starting with `#` are ignored.
+    pub fn load(path: &std::path::Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("when reading the wordlist {}", path.display()))?;
+        Ok(Self::new(
+            contents
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.starts_with('#')),
+        ))
+    }
+}
+
+impl TextFilter for Wordlist {
+    fn find(&self, text: &str) -> Vec<Range<usize>> {
+        let text = lowercase(text);
+        let mut matches = Vec::new();
+        for word in &self.words {
+            for start in 0..text.len() {
+                if text[start..].starts_with(word) {
+                    matches.push(start..start + word.len());
+                }
+            }
+        }
+        matches
+    }
+}
+
+/// Lowercases every character separately to keep the character positions intact.
+fn lowercase(text: &str) -> Vec<char> {
+    text.chars()
+        .map(|c| c.to_lowercase().next().unwrap_or(c))
+        .collect()
+}
+
+/// Text that passed the filter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Filtered {
+    pub text: String,
+    /// Whether the text needs to be reviewed by a moderator.
+    pub flagged: bool,
+}
+
+/// Filter applied to player names and score info before they are stored.
+pub struct ContentFilter {
+    filter: Box<dyn TextFilter>,
+    mode: FilterMode,
+}
+
+impl ContentFilter {
+    pub fn new(filter: Box<dyn TextFilter>, mode: FilterMode) -> Self {
+        Self { filter, mode }
+    }
+
+    /// Sets up the filter described by the config.
+    /// Without a wordlist nothing is filtered.
+    pub fn from_config(config: &FilterConfig) -> Result<Self> {
+        let wordlist = match &config.wordlist {
+            Some(path) => Wordlist::load(path)?,
+            None => Wordlist::default(),
+        };
+        Ok(Self::new(Box::new(wordlist), config.mode))
+    }
+
+    /// Passes the text through the filter.
+    /// `field` names the text in the error if it is rejected.
+    pub fn apply(&self, field: &'static str, text: &str) -> RequestResult<Filtered> {
+        let matches = self.filter.find(text);
+        if matches.is_empty() {
+            return Ok(Filtered {
+                text: text.to_owned(),
+                flagged: false,
+            });
+        }
+
+        match self.mode {
+            FilterMode::Reject => Err(RequestError::RejectedText(field)),
+            FilterMode::Mask => {
+                let text = text
+                    .chars()
+                    .enumerate()
+                    .map(|(i, c)| {
+                        if matches.iter().any(|range| range.contains(&i)) {
+                            '*'
+                        } else {
+                            c
+                        }
+                    })
+                    .collect();
+                Ok(Filtered {
+                    text,
+                    flagged: false,
+                })
+            }
+            FilterMode::Flag => Ok(Filtered {
+                text: text.to_owned(),
+                flagged: true,
+            }),
+        }
+    }
+}
@@ -1,30 +1,67 @@
+mod admin;
 mod api_key;
+mod config;
 mod database;
+mod filter;
 mod prelude;
 mod server;
 mod setup;
 
 use self::prelude::*;
 
+use color_eyre::eyre::bail;
+use std::path::PathBuf;
+
 #[derive(clap::Parser)]
+/// Leaderboard server.
 struct Opts {
-    port: u16,
+    /// Config file in TOML format.
+    /// Options on the command line take priority over it.
+    #[arg(long, short)]
+    config: Option<PathBuf>,
+    #[command(flatten)]
+    args: config::ConfigArgs,
+    #[command(subcommand)]
+    command: Option<admin::Command>,
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    // Panic handler
+    color_eyre::install()?;
+
+    // Load .env before parsing, so that it can provide options
+    dotenv::dotenv().ok(); // Error if file does not exist: ignore
+
     let opts: Opts = clap::Parser::parse();
 
-    setup::setup()?;
+    let mut config = match &opts.config {
+        Some(path) => config::Config::load(path)?,
+        None => config::Config::default(),
+    };
+    opts.args.apply(&mut config);
+    config.validate().context("invalid configuration")?;
 
-    let database_url =
-        dotenv::var("DATABASE_URL").expect("DATABASE_URL environment variable is not set");
+    let Some(database_url) = config.server.database_url.clone() else {
+        bail!(
+            "database url is not set: use --database-url, \
+            the DATABASE_URL environment variable or server.database_url in the config file"
+        );
+    };
 
-    let database_pool = setup::connect_database(&database_url)
+    let command = opts.command.unwrap_or(admin::Command::Serve);
+    let serve = matches!(command, admin::Command::Serve);
+    setup::setup(config.server.log_format, !serve)?;
+
+    let database_pool = setup::connect_database(&database_url, config.server.pool_size)
         .await
         .context(format!("when connecting to the database: {}", database_url))?;
 
-    server::run(opts.port, database_pool)
-        .await
-        .context("server error")
+    if serve {
+        server::run(database_pool, config)
+            .await
+            .context("server error")
+    } else {
+        admin::run(&database_pool, &config, command, &mut std::io::stdout()).await
+    }
 }
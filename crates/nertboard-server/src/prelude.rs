@@ -1,4 +1,4 @@
 pub use std::sync::Arc;
 
 pub use color_eyre::{eyre::Context, Result};
-pub use tracing::{debug, error, info};
+pub use tracing::{error, info};
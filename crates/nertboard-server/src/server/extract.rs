@@ -0,0 +1,28 @@
+//! Wrappers around axum extractors that reject with a [`RequestError`],
+//! so that every error is reported in the same json format.
+
+use crate::database::RequestError;
+
+use axum::{
+    extract::{FromRequest, FromRequestParts},
+    response::IntoResponse,
+};
+use serde::Serialize;
+
+#[derive(FromRequest)]
+#[from_request(via(axum::Json), rejection(RequestError))]
+pub struct Json<T>(pub T);
+
+impl<T: Serialize> IntoResponse for Json<T> {
+    fn into_response(self) -> axum::response::Response {
+        axum::Json(self.0).into_response()
+    }
+}
+
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Path), rejection(RequestError))]
+pub struct Path<T>(pub T);
+
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Query), rejection(RequestError))]
+pub struct Query<T>(pub T);
@@ -0,0 +1,136 @@
+use super::*;
+
+use nertboard_core::{BoardInfo, BoardInfoUpdate, BoardQuery};
+
+/// Maximum length of a display name in characters.
+const MAX_DISPLAY_NAME_LENGTH: usize = 64;
+/// Maximum length of a description in characters.
+const MAX_DESCRIPTION_LENGTH: usize = 1024;
+
+/// Fetches the information about boards, all of them if `board_id` is `None`.
+async fn fetch_info(
+    database: &DatabasePool,
+    board_id: Option<Id>,
+    query: &BoardQuery,
+) -> Result<Vec<BoardInfo>> {
+    let boards = sqlx::query(
+        "
+SELECT board_name, display_name, description, created_at,
+    score_order, score_policy, public_read,
+    min_score, max_score, submission_count, submission_window, max_info_length,
+    (
+        SELECT COUNT(*) FROM scores
+        WHERE scores.board_id = boards.board_id AND hidden = 0
+    ) AS score_count,
+    (
+        SELECT COUNT(DISTINCT player_id) FROM scores
+        WHERE scores.board_id = boards.board_id AND hidden = 0
+    ) AS player_count
+FROM boards
+WHERE (? OR board_id = ?)
+ORDER BY board_name
+LIMIT ? OFFSET ?
+        ",
+    )
+    .bind(board_id.is_none())
+    .bind(board_id.unwrap_or_default())
+    .bind(query.limit.map_or(i64::MAX, i64::from))
+    .bind(i64::from(query.offset.unwrap_or(0)))
+    .try_map(|row: AnyRow| {
+        Ok(BoardInfo {
+            name: row.try_get("board_name")?,
+            display_name: decode_optional(&row, "display_name")?,
+            description: decode_optional(&row, "description")?,
+            created_at: decode_optional(&row, "created_at")?,
+            score_count: row.try_get("score_count")?,
+            player_count: row.try_get("player_count")?,
+            settings: decode_settings(&row)?,
+        })
+    })
+    .fetch_all(database)
+    .await?;
+    Ok(boards)
+}
+
+/// Lists all boards on the server.
+/// Requires an operator key, unless anyone can create boards.
+pub(super) async fn list_boards(
+    Query(query): Query<BoardQuery>,
+    State(database): State<Arc<DatabasePool>>,
+    State(creation): State<Arc<BoardCreation>>,
+    operator_key: Option<OperatorKey>,
+) -> Result<Json<Vec<BoardInfo>>> {
+    check_operator(&creation, operator_key)?;
+
+    let boards = fetch_info(&database, None, &query).await?;
+    Ok(Json(boards))
+}
+
+pub(super) async fn board_info(
+    Path(board_name): Path<String>,
+    State(database): State<Arc<DatabasePool>>,
+    api_key: Option<ApiKey>,
+) -> Result<Json<BoardInfo>> {
+    let (board, auth) = check_board(Path(board_name), State(database.clone()), api_key).await?;
+    check_read(&board, auth)?;
+
+    let info = fetch_one(&database, board.id).await?;
+    Ok(Json(info))
+}
+
+async fn fetch_one(database: &DatabasePool, board_id: Id) -> Result<BoardInfo> {
+    let info = fetch_info(database, Some(board_id), &BoardQuery::default())
+        .await?
+        .pop()
+        .ok_or(sqlx::Error::RowNotFound)?;
+    Ok(info)
+}
+
+/// Changes the display name and the description of the board.
+pub(super) async fn update_info(
+    Path(board_name): Path<String>,
+    State(database): State<Arc<DatabasePool>>,
+    api_key: Option<ApiKey>,
+    Json(update): Json<BoardInfoUpdate>,
+) -> Result<Json<BoardInfo>> {
+    let (board, auth) = check_board(Path(board_name), State(database.clone()), api_key).await?;
+    check_auth(auth, AuthorityLevel::Admin)?;
+
+    let normalize = |text: Option<String>| text.map(|text| text.trim().to_owned());
+    let display_name = normalize(update.display_name);
+    let description = normalize(update.description);
+    if display_name
+        .as_ref()
+        .is_some_and(|name| name.chars().count() > MAX_DISPLAY_NAME_LENGTH)
+    {
+        return Err(RequestError::InvalidSettings("display name is too long"));
+    }
+    if description
+        .as_ref()
+        .is_some_and(|text| text.chars().count() > MAX_DESCRIPTION_LENGTH)
+    {
+        return Err(RequestError::InvalidSettings("description is too long"));
+    }
+
+    // Missing text keeps the value, empty text removes it.
+    // Merged in a single statement, so that concurrent updates of different fields are all kept
+    let value = |text: &Option<String>| text.clone().filter(|text| !text.is_empty());
+    sqlx::query(
+        "
+UPDATE boards
+SET display_name = CASE WHEN ? THEN ? ELSE display_name END,
+    description = CASE WHEN ? THEN ? ELSE description END
+WHERE board_id = ?
+        ",
+    )
+    .bind(display_name.is_some())
+    .bind(value(&display_name))
+    .bind(description.is_some())
+    .bind(value(&description))
+    .bind(board.id)
+    .execute(&*database)
+    .await?;
+
+    let info = fetch_one(&database, board.id).await?;
+    Ok(Json(info))
+}
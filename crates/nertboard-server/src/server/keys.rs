@@ -0,0 +1,285 @@
+use super::*;
+
+use nertboard_core::{KeyCreate, KeyInfo, NewKey};
+
+fn decode_key_info(row: &AnyRow) -> sqlx::Result<KeyInfo> {
+    Ok(KeyInfo {
+        id: row.try_get("key_id")?,
+        label: row.try_get("label")?,
+        authority: decode_column(row, "authority", KeyAuthority::parse)?,
+        created_at: row.try_get("created_at")?,
+        expires_at: decode_optional(row, "expires_at")?,
+        last_used_at: decode_optional(row, "last_used_at")?,
+    })
+}
+
+/// Generates and stores a new key for the board.
+pub(super) async fn insert_key<'c>(
+    executor: impl sqlx::Executor<'c, Database = sqlx::Any>,
+    key_lengths: &KeyLengths,
+    board_id: Id,
+    label: &str,
+    authority: KeyAuthority,
+    expires_at: Option<i64>,
+) -> Result<NewKey> {
+    let key = StringKey::generate(key_lengths.board_key(authority));
+    let created_at = Utc::now().timestamp();
+
+    let id = sqlx::query(
+        "
+INSERT INTO board_keys (board_id, label, authority, key_hash, created_at, expires_at)
+VALUES (?, ?, ?, ?, ?, ?)
+RETURNING key_id
+        ",
+    )
+    .bind(board_id)
+    .bind(label)
+    .bind(authority.as_str())
+    .bind(HashedKey::hash(key.inner()).as_str())
+    .bind(created_at)
+    .bind(expires_at)
+    .try_map(|row: AnyRow| row.try_get::<Id, _>("key_id"))
+    .fetch_one(executor)
+    .await?;
+
+    Ok(NewKey {
+        info: KeyInfo {
+            id,
+            label: label.to_owned(),
+            authority,
+            created_at,
+            expires_at,
+            last_used_at: None,
+        },
+        key: key.inner().to_owned(),
+    })
+}
+
+/// Keys are marked as used at most once per this many seconds,
+/// so that every authenticated read does not turn into a write.
+const LAST_USED_PRECISION: i64 = 60;
+
+/// Resolves the authority of the key on the board and marks the key as used.
+///
+/// Keys are only stored as salted hashes,
+/// so every active key of the board is checked.
+pub(super) async fn check_key(
+    database: &DatabasePool,
+    board_id: Id,
+    api_key: &ApiKey,
+) -> Result<AuthorityLevel> {
+    let now = Utc::now().timestamp();
+    let keys = sqlx::query(
+        "
+SELECT key_id, authority, key_hash, last_used_at FROM board_keys
+WHERE board_id = ? AND (expires_at IS NULL OR expires_at > ?)
+        ",
+    )
+    .bind(board_id)
+    .bind(now)
+    .try_map(|row: AnyRow| {
+        Ok((
+            row.try_get::<Id, _>("key_id")?,
+            decode_column(&row, "authority", KeyAuthority::parse)?,
+            HashedKey::from_stored(row.try_get::<String, _>("key_hash")?),
+            decode_optional::<i64>(&row, "last_used_at")?,
+        ))
+    })
+    .fetch_all(database)
+    .await?;
+
+    // Verify every key, so that the time taken does not reveal which one matched
+    let mut found = None;
+    for (key_id, authority, hash, last_used_at) in keys {
+        let matches = hash.verify(&api_key.key);
+        if matches && found.is_none() {
+            found = Some((key_id, authority, last_used_at));
+        }
+    }
+    let Some((key_id, authority, last_used_at)) = found else {
+        return Ok(AuthorityLevel::Unauthorized);
+    };
+    if let Some(budget) = &api_key.budget {
+        budget.limit_key(key_id)?;
+    }
+
+    if last_used_at.is_none_or(|last_used_at| now - last_used_at >= LAST_USED_PRECISION) {
+        sqlx::query("UPDATE board_keys SET last_used_at = ? WHERE key_id = ?")
+            .bind(now)
+            .bind(key_id)
+            .execute(database)
+            .await?;
+    }
+
+    Ok(authority.into())
+}
+
+pub(super) async fn list_keys(
+    Path(board_name): Path<String>,
+    State(database): State<Arc<DatabasePool>>,
+    api_key: Option<ApiKey>,
+) -> Result<Json<Vec<KeyInfo>>> {
+    let (board, auth) = check_board(Path(board_name), State(database.clone()), api_key).await?;
+    check_auth(auth, AuthorityLevel::Admin)?;
+
+    let keys = fetch_keys(&*database, board.id).await?;
+    Ok(Json(keys))
+}
+
+/// Fetches the information about all keys of the board, including expired ones.
+pub(crate) async fn fetch_keys<'c>(
+    executor: impl sqlx::Executor<'c, Database = sqlx::Any>,
+    board_id: Id,
+) -> Result<Vec<KeyInfo>> {
+    let keys = sqlx::query(
+        "
+SELECT key_id, label, authority, created_at, expires_at, last_used_at
+FROM board_keys WHERE board_id = ?
+ORDER BY key_id
+        ",
+    )
+    .bind(board_id)
+    .try_map(|row: AnyRow| decode_key_info(&row))
+    .fetch_all(executor)
+    .await?;
+    Ok(keys)
+}
+
+pub(super) async fn create_key(
+    Path(board_name): Path<String>,
+    State(database): State<Arc<DatabasePool>>,
+    State(key_lengths): State<Arc<KeyLengths>>,
+    api_key: Option<ApiKey>,
+    Json(key): Json<KeyCreate>,
+) -> Result<Json<NewKey>> {
+    let (board, auth) = check_board(Path(board_name), State(database.clone()), api_key).await?;
+    check_auth(auth, AuthorityLevel::Admin)?;
+
+    let label = key.label.trim();
+    if label.is_empty() {
+        return Err(RequestError::InvalidSettings("key label cannot be empty"));
+    }
+    if key
+        .expires_at
+        .is_some_and(|expires_at| expires_at <= Utc::now().timestamp())
+    {
+        return Err(RequestError::InvalidSettings(
+            "key expiration time is in the past",
+        ));
+    }
+
+    let key = insert_key(
+        &*database,
+        &key_lengths,
+        board.id,
+        label,
+        key.authority,
+        key.expires_at,
+    )
+    .await?;
+    Ok(Json(key))
+}
+
+/// Fetches the key of the board by id.
+async fn fetch_key(database: &DatabasePool, board_id: Id, key_id: Id) -> Result<KeyInfo> {
+    sqlx::query(
+        "
+SELECT key_id, label, authority, created_at, expires_at, last_used_at
+FROM board_keys WHERE board_id = ? AND key_id = ?
+        ",
+    )
+    .bind(board_id)
+    .bind(key_id)
+    .try_map(|row: AnyRow| decode_key_info(&row))
+    .fetch_optional(database)
+    .await?
+    .ok_or(RequestError::NoSuchKey(key_id))
+}
+
+pub(super) async fn revoke_key(
+    Path((board_name, key_id)): Path<(String, Id)>,
+    State(database): State<Arc<DatabasePool>>,
+    api_key: Option<ApiKey>,
+) -> Result<()> {
+    let (board, auth) = check_board(Path(board_name), State(database.clone()), api_key).await?;
+    check_auth(auth, AuthorityLevel::Admin)?;
+
+    let key = fetch_key(&database, board.id, key_id).await?;
+
+    let mut transaction = database.begin().await?;
+    if let KeyAuthority::Admin = key.authority {
+        // Lock the board first, so that concurrent revocations wait for each other
+        // and the board stays manageable with another working admin key
+        sqlx::query("UPDATE boards SET board_id = board_id WHERE board_id = ?")
+            .bind(board.id)
+            .execute(&mut *transaction)
+            .await?;
+        let other_admins = sqlx::query(
+            "
+SELECT COUNT(*) AS admins FROM board_keys
+WHERE board_id = ? AND authority = ? AND key_id <> ?
+    AND (expires_at IS NULL OR expires_at > ?)
+            ",
+        )
+        .bind(board.id)
+        .bind(KeyAuthority::Admin.as_str())
+        .bind(key_id)
+        .bind(Utc::now().timestamp())
+        .try_map(|row: AnyRow| row.try_get::<i64, _>("admins"))
+        .fetch_one(&mut *transaction)
+        .await?;
+        if other_admins == 0 {
+            return Err(RequestError::LastAdminKey);
+        }
+    }
+
+    sqlx::query("DELETE FROM board_keys WHERE key_id = ?")
+        .bind(key_id)
+        .execute(&mut *transaction)
+        .await?;
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+pub(super) async fn rotate_key(
+    Path((board_name, key_id)): Path<(String, Id)>,
+    State(database): State<Arc<DatabasePool>>,
+    State(key_lengths): State<Arc<KeyLengths>>,
+    api_key: Option<ApiKey>,
+) -> Result<Json<NewKey>> {
+    let (board, auth) = check_board(Path(board_name), State(database.clone()), api_key).await?;
+    check_auth(auth, AuthorityLevel::Admin)?;
+
+    let info = fetch_key(&database, board.id, key_id).await?;
+    let key = replace_key(&*database, &key_lengths, info).await?;
+    Ok(Json(key))
+}
+
+/// Generates a new key in place of the existing one,
+/// the old key stops working immediately.
+pub(crate) async fn replace_key<'c>(
+    executor: impl sqlx::Executor<'c, Database = sqlx::Any>,
+    key_lengths: &KeyLengths,
+    info: KeyInfo,
+) -> Result<NewKey> {
+    let key = StringKey::generate(key_lengths.board_key(info.authority));
+    let created_at = Utc::now().timestamp();
+    sqlx::query(
+        "UPDATE board_keys SET key_hash = ?, created_at = ?, last_used_at = NULL WHERE key_id = ?",
+    )
+    .bind(HashedKey::hash(key.inner()).as_str())
+    .bind(created_at)
+    .bind(info.id)
+    .execute(executor)
+    .await?;
+
+    Ok(NewKey {
+        info: KeyInfo {
+            created_at,
+            last_used_at: None,
+            ..info
+        },
+        key: key.inner().to_owned(),
+    })
+}
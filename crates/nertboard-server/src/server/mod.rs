@@ -1,103 +1,231 @@
+mod extract;
+mod info;
+mod keys;
+mod moderation;
+mod player;
+mod rate_limit;
+mod signing;
 #[cfg(test)]
 mod tests;
+mod validation;
+
+pub(crate) use self::rate_limit::KeyBudget;
+pub(crate) use self::{
+    keys::{fetch_keys, replace_key},
+    moderation::{fetch_records, insert_ban},
+};
+
+use self::extract::{Json, Path, Query};
+use self::rate_limit::{Budget, RateLimiter};
 
 use crate::{
-    api_key::{ApiKey, AuthorityLevel, BoardKeys, PlayerKey, StringKey},
-    database::{DatabasePool, Id, RequestError, RequestResult as Result},
+    api_key::{ApiKey, AuthorityLevel, HashedKey, OperatorKey, PlayerKey, StringKey},
+    config::{BoardCreation, Config, KeyLengths, NameRules, Operator},
+    database::{
+        decode_column, decode_optional, is_unique_violation, Board, DatabasePool, Id, RequestError,
+        RequestResult as Result, Score,
+    },
+    filter::{ContentFilter, Filtered},
     prelude::*,
 };
 
 use axum::{
-    extract::{Path, Query, State},
-    routing::{get, post},
-    Json, Router,
+    extract::{FromRef, State},
+    http::HeaderValue,
+    routing::{delete, get, patch, post, put},
+    Router,
+};
+use chrono::{DateTime, Datelike, NaiveTime, Utc};
+use nertboard_core::{
+    BoardCreate, BoardKeys, BoardSettings, BoardSettingsUpdate, KeyAuthority, ScoreOrder,
+    ScorePolicy, TimePeriod,
 };
 use serde::Deserialize;
 use sqlx::{any::AnyRow, Row};
 use tower_http::{cors::CorsLayer, trace::TraceLayer};
 
-pub async fn run(port: u16, database_pool: DatabasePool) -> color_eyre::Result<()> {
-    let addr = format!("0.0.0.0:{}", port);
+/// State shared by the request handlers.
+#[derive(Clone, FromRef)]
+struct AppState {
+    database: Arc<DatabasePool>,
+    names: Arc<NameRules>,
+    filter: Arc<ContentFilter>,
+    limiter: Arc<RateLimiter>,
+    key_lengths: Arc<KeyLengths>,
+    board_creation: Arc<BoardCreation>,
+    #[from_ref(skip)]
+    cors: CorsLayer,
+}
+
+impl AppState {
+    fn new(database: Arc<DatabasePool>, config: Config) -> color_eyre::Result<Self> {
+        let filter =
+            ContentFilter::from_config(&config.filter).context("when setting up the filter")?;
+        Ok(Self {
+            database,
+            names: Arc::new(config.names),
+            filter: Arc::new(filter),
+            limiter: Arc::new(RateLimiter::new(
+                config.rate_limits,
+                config.server.trusted_proxies.clone(),
+            )),
+            key_lengths: Arc::new(config.key_lengths),
+            board_creation: Arc::new(config.board_creation),
+            cors: cors_layer(&config.server.cors_origins)?,
+        })
+    }
+}
+
+/// Allows cross-origin requests from the origins, `*` allows any origin.
+fn cors_layer(origins: &[String]) -> color_eyre::Result<CorsLayer> {
+    let cors = CorsLayer::new().allow_headers(tower_http::cors::Any);
+    if origins.iter().any(|origin| origin == "*") {
+        return Ok(cors.allow_origin(tower_http::cors::Any));
+    }
+    let origins = origins
+        .iter()
+        .map(|origin| {
+            HeaderValue::from_str(origin)
+                .with_context(|| format!("invalid cors origin: {:?}", origin))
+        })
+        .collect::<color_eyre::Result<Vec<_>>>()?;
+    Ok(cors.allow_origin(origins))
+}
+
+pub async fn run(database_pool: DatabasePool, config: Config) -> color_eyre::Result<()> {
+    let addr = std::net::SocketAddr::new(config.server.address, config.server.port);
     info!("Starting the server on {}", addr);
     let listener = tokio::net::TcpListener::bind(addr)
         .await
         .context("when binding a tcp listener")?;
 
-    axum::serve(listener, app(Arc::new(database_pool))).await?;
+    let creation = &config.board_creation;
+    if creation.open {
+        info!("Anyone can create boards, do not use open board creation in production");
+    } else if creation.operators.is_empty() {
+        info!("No operator keys configured, boards can only be created with admin commands");
+    }
+
+    let state = AppState::new(Arc::new(database_pool), config)?;
+    axum::serve(
+        listener,
+        app(state).into_make_service_with_connect_info::<std::net::SocketAddr>(),
+    )
+    .await?;
     Ok(())
 }
 
-fn app(database_pool: Arc<DatabasePool>) -> Router {
+fn app(state: AppState) -> Router {
     Router::new()
         .route("/", get(get_root))
-        .route("/player/create", post(create_player))
+        .route("/player/create", post(player::create_player))
+        .route(
+            "/player/:player_id/regenerate",
+            post(player::regenerate_key),
+        )
+        .route("/player/:player_id/recover", post(player::recover_player))
+        .route("/player/:player_id/rename", post(player::rename_player))
+        .route("/player/:player_id/names", get(player::name_history))
         .route(
             "/board/:board_name",
             get(get_scores).post(submit_score).delete(delete_board),
         )
         .route("/board/create", post(create_board))
-        .layer(TraceLayer::new_for_http())
-        .layer(
-            CorsLayer::new()
-                .allow_origin(tower_http::cors::Any)
-                .allow_headers(tower_http::cors::Any),
+        .route("/boards", get(info::list_boards))
+        .route(
+            "/board/:board_name/info",
+            get(info::board_info).patch(info::update_info),
+        )
+        .route("/board/:board_name/settings", patch(update_settings))
+        .route("/board/:board_name/player/:player_id", get(get_player_rank))
+        .route(
+            "/board/:board_name/keys",
+            get(keys::list_keys).post(keys::create_key),
+        )
+        .route("/board/:board_name/keys/:key_id", delete(keys::revoke_key))
+        .route(
+            "/board/:board_name/keys/:key_id/rotate",
+            post(keys::rotate_key),
         )
-        .with_state(database_pool)
+        .route("/board/:board_name/scores", get(moderation::list_scores))
+        .route(
+            "/board/:board_name/scores/:score_id",
+            patch(moderation::update_score).delete(moderation::delete_score),
+        )
+        .route(
+            "/board/:board_name/player/:player_id/scores",
+            delete(moderation::wipe_player_scores),
+        )
+        .route(
+            "/board/:board_name/signing",
+            post(signing::enable_signing).delete(signing::disable_signing),
+        )
+        .route("/board/:board_name/bans", get(moderation::list_bans))
+        .route(
+            "/board/:board_name/bans/:player_id",
+            put(moderation::ban_player).delete(moderation::unban_player),
+        )
+        .route_layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            rate_limit::limit_requests,
+        ))
+        .fallback(|| async { RequestError::RouteNotFound })
+        .method_not_allowed_fallback(|| async { RequestError::MethodNotAllowed })
+        .layer(TraceLayer::new_for_http())
+        .layer(state.cors.clone())
+        .with_state(state)
 }
 
 async fn get_root() -> &'static str {
     "Hello, world"
 }
 
-async fn create_player(
-    State(database): State<Arc<DatabasePool>>,
-    Json(player_name): Json<String>,
-) -> Result<Json<nertboard_core::Player>> {
-    // Generate a random key
-    let key = StringKey::generate(10).inner().to_owned();
-
-    let id = sqlx::query("INSERT INTO players (key, name) VALUES (?, ?) RETURNING player_id")
-        .bind(&key)
-        .bind(&player_name)
-        .try_map(|row: AnyRow| row.try_get::<Id, _>("player_id"))
-        .fetch_one(&*database)
-        .await?;
-
-    Ok(Json(nertboard_core::Player {
-        id,
-        key,
-        name: player_name,
-    }))
-}
-
-/// Queries information about the board by name and returns its id
+/// Queries information about the board by name and returns it
 /// together with the authority level of the provided api key.
 async fn check_board(
     Path(board_name): Path<String>,
     State(database): State<Arc<DatabasePool>>,
     api_key: Option<ApiKey>,
-) -> Result<(Id, AuthorityLevel)> {
+) -> Result<(Board, AuthorityLevel)> {
+    let board = find_board(&database, &board_name).await?;
+    let authority = match api_key {
+        None => AuthorityLevel::Unauthorized,
+        Some(key) => keys::check_key(&database, board.id, &key).await?,
+    };
+    Ok((board, authority))
+}
+
+/// Queries information about the board by name.
+pub(crate) async fn find_board(database: &DatabasePool, board_name: &str) -> Result<Board> {
     let board_row = sqlx::query(
-        "SELECT board_id, read_key, submit_key, admin_key FROM boards WHERE board_name = ?",
+        "
+SELECT board_id, score_order, score_policy, public_read,
+    min_score, max_score, submission_count, submission_window, max_info_length
+FROM boards WHERE board_name = ?
+        ",
     )
-    .bind(&board_name)
-    .fetch_optional(&*database)
+    .bind(board_name)
+    .fetch_optional(database)
     .await?;
 
     let Some(row) = board_row else {
-        return Err(RequestError::NoSuchBoard(board_name.clone()));
+        return Err(RequestError::NoSuchBoard(board_name.to_owned()));
     };
 
-    let board_id: i32 = row.try_get("board_id")?;
-    let keys = BoardKeys {
-        read: StringKey::new(row.try_get::<String, _>("read_key")?),
-        submit: StringKey::new(row.try_get::<String, _>("submit_key")?),
-        admin: StringKey::new(row.try_get::<String, _>("admin_key")?),
-    };
-    let authority = api_key.map_or(AuthorityLevel::Unauthorized, |key| {
-        keys.check_authority(&key.0)
-    });
-    Ok((board_id, authority))
+    Ok(Board {
+        id: row.try_get("board_id")?,
+        settings: decode_settings(&row)?,
+    })
+}
+
+/// Decodes the settings from a row of the `boards` table.
+fn decode_settings(row: &AnyRow) -> sqlx::Result<BoardSettings> {
+    Ok(BoardSettings {
+        order: decode_column(row, "score_order", ScoreOrder::parse)?,
+        policy: decode_column(row, "score_policy", ScorePolicy::parse)?,
+        public_read: row.try_get::<i32, _>("public_read")? != 0,
+        validation: validation::decode_validation(row)?,
+    })
 }
 
 fn check_auth(auth: AuthorityLevel, required: AuthorityLevel) -> Result<()> {
@@ -110,6 +238,14 @@ fn check_auth(auth: AuthorityLevel, required: AuthorityLevel) -> Result<()> {
     }
 }
 
+/// Checks that scores on the board can be read with the given authority.
+fn check_read(board: &Board, auth: AuthorityLevel) -> Result<()> {
+    if board.settings.public_read {
+        return Ok(());
+    }
+    check_auth(auth, AuthorityLevel::Read)
+}
+
 fn validate_board_name(name: String) -> Result<String> {
     let name = name.trim().to_owned();
     if name.is_empty() {
@@ -118,37 +254,138 @@ fn validate_board_name(name: String) -> Result<String> {
     Ok(name)
 }
 
+/// Resolves the operator by key.
+/// Without a key, only succeeds in the open creation mode.
+fn check_operator(
+    creation: &BoardCreation,
+    operator_key: Option<OperatorKey>,
+) -> Result<Option<&Operator>> {
+    match operator_key {
+        None if creation.open => Ok(None),
+        None => Err(RequestError::Unathorized),
+        Some(key) => creation
+            .find_operator(&key.0)
+            .map(Some)
+            .ok_or(RequestError::Unathorized),
+    }
+}
+
 async fn create_board(
     State(database): State<Arc<DatabasePool>>,
-    Json(board_name): Json<String>,
+    State(key_lengths): State<Arc<KeyLengths>>,
+    State(creation): State<Arc<BoardCreation>>,
+    operator_key: Option<OperatorKey>,
+    Json(board): Json<BoardCreate>,
 ) -> Result<Json<BoardKeys>> {
-    // Validate the name
-    let board_name = validate_board_name(board_name)?;
+    let operator = check_operator(&creation, operator_key)?;
+    let keys = insert_board(&database, &key_lengths, board, operator).await?;
+    Ok(Json(keys))
+}
 
-    // Check if a board with this name already exists
-    let check = check_board(Path(board_name.clone()), State(database.clone()), None).await;
-    if check.is_ok() {
-        return Err(RequestError::BoardAlreadyExists(board_name));
+/// Creates the board with a key of every authority.
+/// `operator` is recorded as the creator of the board and limited by its quota, if any.
+pub(crate) async fn insert_board(
+    database: &DatabasePool,
+    key_lengths: &KeyLengths,
+    board: BoardCreate,
+    operator: Option<&Operator>,
+) -> Result<BoardKeys> {
+    // Validate the name
+    let board_name = validate_board_name(board.name)?;
+    validation::check_rules(&board.settings.validation)?;
+
+    if let Some(operator) = operator.filter(|operator| operator.max_boards.is_some()) {
+        // Outside of the transaction, a failed statement aborts the whole transaction in Postgres
+        let inserted = sqlx::query(
+            "
+INSERT INTO operators (operator_name)
+SELECT ? WHERE NOT EXISTS (SELECT 1 FROM operators WHERE operator_name = ?)
+            ",
+        )
+        .bind(&operator.name)
+        .bind(&operator.name)
+        .execute(database)
+        .await;
+        match inserted {
+            // Inserted concurrently by another request
+            Err(error) if is_unique_violation(&error) => {}
+            inserted => {
+                inserted?;
+            }
+        }
     }
 
-    // Generate keys
-    let keys = BoardKeys::generate();
+    let mut transaction = database.begin().await?;
+
+    if let Some(operator) = operator {
+        if let Some(max_boards) = operator.max_boards {
+            // Lock the operator first, so that concurrent requests of the operator
+            // wait for each other and count the boards committed in the meantime
+            sqlx::query(
+                "UPDATE operators SET operator_name = operator_name WHERE operator_name = ?",
+            )
+            .bind(&operator.name)
+            .execute(&mut *transaction)
+            .await?;
+            let boards = sqlx::query("SELECT COUNT(*) AS boards FROM boards WHERE created_by = ?")
+                .bind(&operator.name)
+                .try_map(|row: AnyRow| row.try_get::<i64, _>("boards"))
+                .fetch_one(&mut *transaction)
+                .await?;
+            if boards >= i64::from(max_boards) {
+                return Err(RequestError::QuotaExceeded(max_boards));
+            }
+        }
+    }
 
-    // Create an entry
-    sqlx::query(
+    // Create an entry, the names are unique
+    let board_id = sqlx::query(
         "
-INSERT INTO boards (board_name, read_key, submit_key, admin_key)
-VALUES (?, ?, ?, ?)
+INSERT INTO boards (board_name, score_order, score_policy, public_read, created_by, created_at)
+VALUES (?, ?, ?, ?, ?, ?)
+RETURNING board_id
         ",
     )
-    .bind(board_name)
-    .bind(keys.read.inner())
-    .bind(keys.submit.inner())
-    .bind(keys.admin.inner())
-    .execute(&*database)
-    .await?;
+    .bind(&board_name)
+    .bind(board.settings.order.as_str())
+    .bind(board.settings.policy.as_str())
+    .bind(i32::from(board.settings.public_read))
+    .bind(operator.map(|operator| operator.name.as_str()))
+    .bind(Utc::now().timestamp())
+    .try_map(|row: AnyRow| row.try_get::<Id, _>("board_id"))
+    .fetch_one(&mut *transaction)
+    .await;
+    let board_id = match board_id {
+        Ok(board_id) => board_id,
+        Err(error) if is_unique_violation(&error) => {
+            return Err(RequestError::BoardAlreadyExists(board_name));
+        }
+        Err(error) => return Err(error.into()),
+    };
+    validation::save_rules(&mut *transaction, board_id, &board.settings.validation).await?;
 
-    Ok(Json(keys))
+    // Generate keys
+    let mut new_key = async |authority: KeyAuthority| -> Result<String> {
+        let key = keys::insert_key(
+            &mut *transaction,
+            key_lengths,
+            board_id,
+            authority.as_str(),
+            authority,
+            None,
+        )
+        .await?;
+        Ok(key.key)
+    };
+    let keys = BoardKeys {
+        read: new_key(KeyAuthority::Read).await?,
+        submit: new_key(KeyAuthority::Submit).await?,
+        admin: new_key(KeyAuthority::Admin).await?,
+    };
+
+    transaction.commit().await?;
+
+    Ok(keys)
 }
 
 async fn delete_board(
@@ -156,72 +393,182 @@ async fn delete_board(
     State(database): State<Arc<DatabasePool>>,
     api_key: Option<ApiKey>,
 ) -> Result<()> {
-    let (board_id, auth) = check_board(Path(board_name), State(database.clone()), api_key).await?;
+    let (board, auth) = check_board(Path(board_name), State(database.clone()), api_key).await?;
     check_auth(auth, AuthorityLevel::Admin)?;
 
+    remove_board(&database, board.id).await
+}
+
+/// Deletes the board with all its scores, keys and history.
+pub(crate) async fn remove_board(database: &DatabasePool, board_id: Id) -> Result<()> {
+    let mut transaction = database.begin().await?;
+
     // Delete scores
     sqlx::query("DELETE FROM scores WHERE board_id = ?")
         .bind(board_id)
-        .execute(&*database)
+        .execute(&mut *transaction)
+        .await?;
+
+    // Delete keys
+    sqlx::query("DELETE FROM board_keys WHERE board_id = ?")
+        .bind(board_id)
+        .execute(&mut *transaction)
+        .await?;
+
+    // Delete submission history
+    sqlx::query("DELETE FROM submissions WHERE board_id = ?")
+        .bind(board_id)
+        .execute(&mut *transaction)
+        .await?;
+
+    // Delete used nonces
+    sqlx::query("DELETE FROM submission_nonces WHERE board_id = ?")
+        .bind(board_id)
+        .execute(&mut *transaction)
+        .await?;
+
+    // Delete bans
+    sqlx::query("DELETE FROM board_bans WHERE board_id = ?")
+        .bind(board_id)
+        .execute(&mut *transaction)
         .await?;
 
     // Delete entry
     sqlx::query("DELETE FROM boards WHERE board_id = ?")
         .bind(board_id)
-        .execute(&*database)
+        .execute(&mut *transaction)
         .await?;
 
+    transaction.commit().await?;
+
     Ok(())
 }
 
+async fn update_settings(
+    Path(board_name): Path<String>,
+    State(database): State<Arc<DatabasePool>>,
+    api_key: Option<ApiKey>,
+    Json(update): Json<BoardSettingsUpdate>,
+) -> Result<Json<BoardSettings>> {
+    let (board, auth) = check_board(Path(board_name), State(database.clone()), api_key).await?;
+    check_auth(auth, AuthorityLevel::Admin)?;
+
+    let mut settings = board.settings;
+    if let Some(public_read) = update.public_read {
+        settings.public_read = public_read;
+    }
+    if let Some(rules) = update.validation {
+        validation::check_rules(&rules)?;
+        settings.validation = rules;
+    }
+
+    let mut transaction = database.begin().await?;
+    sqlx::query("UPDATE boards SET public_read = ? WHERE board_id = ?")
+        .bind(i32::from(settings.public_read))
+        .bind(board.id)
+        .execute(&mut *transaction)
+        .await?;
+    validation::save_rules(&mut *transaction, board.id, &settings.validation).await?;
+    transaction.commit().await?;
+
+    Ok(Json(settings))
+}
+
+#[allow(clippy::too_many_arguments)]
 async fn submit_score(
     Path(board_name): Path<String>,
     Query(PlayerIdQuery { player_id }): Query<PlayerIdQuery>,
     State(database): State<Arc<DatabasePool>>,
+    State(filter): State<Arc<ContentFilter>>,
+    State(limiter): State<Arc<RateLimiter>>,
     api_key: Option<ApiKey>,
     player_key: PlayerKey,
+    signature: Option<signing::SubmissionSignature>,
     Json(score): Json<nertboard_core::ScoreEntry>,
-) -> Result<()> {
+) -> Result<Json<nertboard_core::SubmitResult>> {
     // Authorize player
-    let (real_key, name) = sqlx::query("SELECT key, name FROM players WHERE player_id = ?")
-        .bind(player_id)
-        .try_map(|row: AnyRow| {
-            Ok((
-                row.try_get::<String, _>("key")?,
-                row.try_get::<String, _>("name")?,
-            ))
-        })
-        .fetch_one(&*database)
-        .await?;
-
-    if real_key != player_key.0 {
-        // Invalid key
-        return Err(RequestError::InvalidPlayer);
-    }
-
-    if name != score.player {
-        // Name changed
-        sqlx::query("UPDATE players SET name = ? WHERE player_id = ?")
-            .bind(&score.player)
-            .bind(player_id)
-            .execute(&*database)
-            .await?;
-    }
+    player::check_player(&database, player_id, &player_key.0).await?;
+    limiter.limit_player(Budget::Submit, player_id)?;
 
     // Access the board
-    let (board_id, auth) = check_board(Path(board_name), State(database.clone()), api_key).await?;
+    let (board, auth) =
+        check_board(Path(board_name.clone()), State(database.clone()), api_key).await?;
     check_auth(auth, AuthorityLevel::Submit)?;
+    if moderation::is_banned(&database, board.id, player_id).await? {
+        return Err(RequestError::PlayerBanned);
+    }
+    let now = Utc::now().timestamp();
+    signing::verify_submission(
+        &database,
+        &board,
+        &board_name,
+        player_id,
+        &score,
+        signature,
+        now,
+    )
+    .await?;
+    validation::validate_score(&board, &score)?;
+
+    let extra_info = score
+        .extra_info
+        .as_deref()
+        .map(|info| filter.apply("extra info", info))
+        .transpose()?;
+
+    let mut transaction = database.begin().await?;
+    validation::record_submission(&mut transaction, &board, player_id, now).await?;
+
+    // Compare with the best score of the player among all of them,
+    // the board might show a worse one, e.g. the latest
+    let all_scores = Board {
+        settings: BoardSettings {
+            policy: ScorePolicy::All,
+            ..board.settings.clone()
+        },
+        ..board.clone()
+    };
+    let previous_best = fetch_player_best(&mut *transaction, &all_scores, player_id)
+        .await?
+        .map(|(_, entry)| entry.score);
+    let improved =
+        previous_best.is_none_or(|best| board.settings.order.is_better(score.score, best));
+
+    // Every score is kept, the board policy is applied when reading,
+    // so that a score can still be shown for the periods it was submitted in
+    let score_id = sqlx::query(
+        "
+INSERT INTO scores (board_id, player_id, score, extra_info, submitted_at, flagged)
+VALUES (?, ?, ?, ?, ?, ?)
+RETURNING score_id
+        ",
+    )
+    .bind(board.id)
+    .bind(player_id)
+    .bind(score.score)
+    .bind(extra_info.as_ref().map(|info| info.text.as_str()))
+    .bind(now)
+    .bind(i32::from(
+        extra_info.as_ref().is_some_and(|info| info.flagged),
+    ))
+    .try_map(|row: AnyRow| row.try_get::<Id, _>("score_id"))
+    .fetch_one(&mut *transaction)
+    .await?;
 
-    // Insert a new score
-    sqlx::query("INSERT INTO scores (board_id, player_id, score, extra_info) VALUES (?, ?, ?, ?)")
-        .bind(board_id)
-        .bind(player_id)
-        .bind(score.score)
-        .bind(&score.extra_info)
-        .execute(&*database)
-        .await?;
+    transaction.commit().await?;
 
-    Ok(())
+    // Find the new rank
+    let (best_id, best) = fetch_player_best(&*database, &board, player_id)
+        .await?
+        .ok_or(sqlx::Error::RowNotFound)?;
+    let rank = count_higher(&database, &board, best_id, best.score).await? + 1;
+
+    Ok(Json(nertboard_core::SubmitResult {
+        score_id,
+        rank,
+        previous_best,
+        improved,
+    }))
 }
 
 #[derive(Deserialize)]
@@ -231,31 +578,286 @@ struct PlayerIdQuery {
 
 async fn get_scores(
     Path(board_name): Path<String>,
+    Query(query): Query<nertboard_core::ScoreQuery>,
     State(database): State<Arc<DatabasePool>>,
     api_key: Option<ApiKey>,
-) -> Result<Json<Vec<nertboard_core::ScoreEntry>>> {
-    let (board_id, auth) = check_board(Path(board_name), State(database.clone()), api_key).await?;
-    check_auth(auth, AuthorityLevel::Read)?;
+) -> Result<Json<nertboard_core::ScoresPage>> {
+    let (board, auth) = check_board(Path(board_name), State(database.clone()), api_key).await?;
+    check_read(&board, auth)?;
+
+    let order = query.order.unwrap_or(board.settings.order);
+    let range = TimeRange::from_query(&query, Utc::now());
+
+    // Count scores
+    let total = bind_shown(
+        sqlx::query(&format!(
+            "SELECT COUNT(*) AS total FROM scores WHERE {}",
+            shown_scores(&board)
+        )),
+        board.id,
+        range,
+    )
+    .try_map(|row: AnyRow| row.try_get::<i64, _>("total"))
+    .fetch_one(&*database)
+    .await?;
 
     // Fetch scores
-    let scores = sqlx::query(
+    let scores = fetch_entries(
+        &database,
+        &board,
+        order,
+        range,
+        query.limit.map(u64::from),
+        query.offset.unwrap_or(0).into(),
+    )
+    .await?;
+
+    Ok(Json(nertboard_core::ScoresPage {
+        total: total as u64,
+        scores,
+    }))
+}
+
+fn order_sql(order: ScoreOrder) -> &'static str {
+    match order {
+        ScoreOrder::Ascending => "ASC",
+        ScoreOrder::Descending => "DESC",
+    }
+}
+
+/// Decodes a score joined with the player name.
+fn decode_entry(row: &AnyRow) -> sqlx::Result<nertboard_core::ScoreEntry> {
+    Ok(nertboard_core::ScoreEntry {
+        player: row.try_get("player_name")?,
+        score: row.try_get("score")?,
+        extra_info: row.try_get("extra_info").ok(),
+        submitted_at: Some(row.try_get("submitted_at")?),
+    })
+}
+
+/// Range of submission times as unix timestamps in seconds.
+#[derive(Debug, Clone, Copy, Default)]
+struct TimeRange {
+    since: Option<i64>,
+    until: Option<i64>,
+}
+
+impl TimeRange {
+    fn from_query(query: &nertboard_core::ScoreQuery, now: DateTime<Utc>) -> Self {
+        let period_start = query.period.map(|period| period_start(period, now));
+        Self {
+            since: period_start.into_iter().chain(query.since).max(),
+            until: query.until,
+        }
+    }
+}
+
+/// Returns the unix timestamp of the start of the current period.
+fn period_start(period: TimePeriod, now: DateTime<Utc>) -> i64 {
+    let today = now.date_naive();
+    let start = match period {
+        TimePeriod::Today => today,
+        TimePeriod::Week => {
+            today - chrono::Days::new(today.weekday().num_days_from_monday().into())
+        }
+        TimePeriod::Month => today.with_day(1).expect("first day of the month is valid"),
+    };
+    start.and_time(NaiveTime::MIN).and_utc().timestamp()
+}
+
+type AnyQuery<'q> = sqlx::query::Query<'q, sqlx::Any, sqlx::any::AnyArguments<'q>>;
+
+/// Condition on the `scores` table selecting the scores shown on the board
+/// in the time range, with the parameters bound by [`bind_shown`].
+///
+/// Every submitted score is stored, and the board policy picks a single score
+/// of each player among the ones in the range, so that the best score of today
+/// is shown even if the player did better last month.
+fn shown_scores(board: &Board) -> String {
+    let superseded = match board.settings.policy {
+        ScorePolicy::All => "1 = 0".to_owned(),
+        ScorePolicy::Best => format!(
+            "other.score {} scores.score
+            OR (other.score = scores.score AND other.score_id < scores.score_id)",
+            better_sql(board.settings.order)
+        ),
+        ScorePolicy::Latest => "other.score_id > scores.score_id".to_owned(),
+    };
+    format!(
         "
-SELECT players.name AS player_name, score, extra_info
+scores.board_id = ? AND scores.hidden = 0
+AND scores.submitted_at >= ? AND scores.submitted_at < ?
+AND NOT EXISTS (
+    SELECT 1 FROM scores AS other
+    WHERE other.board_id = scores.board_id AND other.player_id = scores.player_id
+        AND other.hidden = 0 AND other.submitted_at >= ? AND other.submitted_at < ?
+        AND ({superseded})
+)
+        "
+    )
+}
+
+/// Binds the parameters of [`shown_scores`].
+fn bind_shown(query: AnyQuery<'_>, board_id: Id, range: TimeRange) -> AnyQuery<'_> {
+    let since = range.since.unwrap_or(i64::MIN);
+    let until = range.until.unwrap_or(i64::MAX);
+    query
+        .bind(board_id)
+        .bind(since)
+        .bind(until)
+        .bind(since)
+        .bind(until)
+}
+
+/// Fetches a range of scores shown on the board in the given order.
+async fn fetch_entries(
+    database: &DatabasePool,
+    board: &Board,
+    order: ScoreOrder,
+    range: TimeRange,
+    limit: Option<u64>,
+    offset: u64,
+) -> Result<Vec<nertboard_core::ScoreEntry>> {
+    let query = format!(
+        "
+SELECT players.name AS player_name, score, extra_info, submitted_at
 FROM scores
 JOIN players ON scores.player_id = players.player_id
-WHERE board_id = ?
+WHERE {}
+ORDER BY score {}, score_id ASC
+LIMIT ? OFFSET ?
         ",
+        shown_scores(board),
+        order_sql(order)
+    );
+    let scores = bind_shown(sqlx::query(&query), board.id, range)
+        .bind(limit.map_or(i64::MAX, |limit| limit as i64))
+        .bind(offset as i64)
+        .try_map(|row: AnyRow| decode_entry(&row))
+        .fetch_all(database)
+        .await?;
+    Ok(scores)
+}
+
+#[derive(Deserialize)]
+struct RankQuery {
+    /// Number of scores to show above and below the player.
+    #[serde(default = "default_rank_window")]
+    window: u32,
+}
+
+fn default_rank_window() -> u32 {
+    5
+}
+
+/// Maximum number of scores shown on each side of the player.
+const MAX_RANK_WINDOW: u32 = 50;
+
+async fn get_player_rank(
+    Path((board_name, player_id)): Path<(String, Id)>,
+    Query(query): Query<RankQuery>,
+    State(database): State<Arc<DatabasePool>>,
+    api_key: Option<ApiKey>,
+) -> Result<Json<nertboard_core::PlayerRank>> {
+    let (board, auth) = check_board(Path(board_name), State(database.clone()), api_key).await?;
+    check_read(&board, auth)?;
+
+    let order = board.settings.order;
+    let window = u64::from(query.window.min(MAX_RANK_WINDOW));
+
+    // Find the best score of the player
+    let best = fetch_player_best(&*database, &board, player_id).await?;
+    let Some((score_id, score)) = best else {
+        return Err(RequestError::NoPlayerScore(player_id));
+    };
+
+    let higher = count_higher(&database, &board, score_id, score.score).await?;
+
+    // Fetch the neighbours
+    let above_count = window.min(higher);
+    let above = fetch_entries(
+        &database,
+        &board,
+        order,
+        TimeRange::default(),
+        Some(above_count),
+        higher - above_count,
+    )
+    .await?;
+    let below = fetch_entries(
+        &database,
+        &board,
+        order,
+        TimeRange::default(),
+        Some(window),
+        higher + 1,
     )
-    .bind(board_id)
-    .try_map(|row: AnyRow| {
-        Ok(nertboard_core::ScoreEntry {
-            player: row.try_get("player_name")?,
-            score: row.try_get("score")?,
-            extra_info: row.try_get("extra_info").ok(),
-        })
-    })
-    .fetch_all(&*database)
     .await?;
 
-    Ok(Json(scores))
+    Ok(Json(nertboard_core::PlayerRank {
+        rank: higher + 1,
+        score,
+        above,
+        below,
+    }))
+}
+
+/// Finds the score of the player shown on the board together with its id,
+/// the best one if the board shows all scores.
+async fn fetch_player_best<'c>(
+    executor: impl sqlx::Executor<'c, Database = sqlx::Any>,
+    board: &Board,
+    player_id: Id,
+) -> Result<Option<(Id, nertboard_core::ScoreEntry)>> {
+    let query = format!(
+        "
+SELECT score_id, players.name AS player_name, score, extra_info, submitted_at
+FROM scores
+JOIN players ON scores.player_id = players.player_id
+WHERE {} AND scores.player_id = ?
+ORDER BY score {}, score_id ASC
+LIMIT 1
+        ",
+        shown_scores(board),
+        order_sql(board.settings.order)
+    );
+    let best = bind_shown(sqlx::query(&query), board.id, TimeRange::default())
+        .bind(player_id)
+        .try_map(|row: AnyRow| Ok((row.try_get::<Id, _>("score_id")?, decode_entry(&row)?)))
+        .fetch_optional(executor)
+        .await?;
+    Ok(best)
+}
+
+fn better_sql(order: ScoreOrder) -> &'static str {
+    match order {
+        ScoreOrder::Ascending => "<",
+        ScoreOrder::Descending => ">",
+    }
+}
+
+/// Counts the number of scores shown on the board ranked higher than the given one.
+async fn count_higher(
+    database: &DatabasePool,
+    board: &Board,
+    score_id: Id,
+    score: Score,
+) -> Result<u64> {
+    let query = format!(
+        "
+SELECT COUNT(*) AS higher
+FROM scores
+WHERE {} AND (scores.score {better} ? OR (scores.score = ? AND scores.score_id < ?))
+        ",
+        shown_scores(board),
+        better = better_sql(board.settings.order)
+    );
+    let higher = bind_shown(sqlx::query(&query), board.id, TimeRange::default())
+        .bind(score)
+        .bind(score)
+        .bind(score_id)
+        .try_map(|row: AnyRow| row.try_get::<i64, _>("higher"))
+        .fetch_one(database)
+        .await?;
+    Ok(higher as u64)
 }
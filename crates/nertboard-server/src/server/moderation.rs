@@ -0,0 +1,249 @@
+use super::*;
+
+use nertboard_core::{BanInfo, RecordQuery, ScoreRecord, ScoreUpdate};
+
+fn decode_record(row: &AnyRow) -> sqlx::Result<ScoreRecord> {
+    Ok(ScoreRecord {
+        id: row.try_get("score_id")?,
+        player_id: row.try_get("player_id")?,
+        player_name: row.try_get("player_name")?,
+        score: row.try_get("score")?,
+        extra_info: row.try_get("extra_info").ok(),
+        submitted_at: row.try_get("submitted_at")?,
+        hidden: row.try_get::<i32, _>("hidden")? != 0,
+        flagged: row.try_get::<i32, _>("flagged")? != 0,
+    })
+}
+
+/// Lists the scores of the board in the order of submission,
+/// including hidden ones.
+pub(super) async fn list_scores(
+    Path(board_name): Path<String>,
+    Query(query): Query<RecordQuery>,
+    State(database): State<Arc<DatabasePool>>,
+    api_key: Option<ApiKey>,
+) -> Result<Json<Vec<ScoreRecord>>> {
+    let (board, auth) = check_board(Path(board_name), State(database.clone()), api_key).await?;
+    check_auth(auth, AuthorityLevel::Admin)?;
+
+    let records = fetch_records(&database, board.id, &query).await?;
+    Ok(Json(records))
+}
+
+/// Fetches the scores of the board matching the query in the order of submission.
+pub(crate) async fn fetch_records(
+    database: &DatabasePool,
+    board_id: Id,
+    query: &RecordQuery,
+) -> Result<Vec<ScoreRecord>> {
+    let records = sqlx::query(
+        "
+SELECT score_id, scores.player_id, players.name AS player_name,
+    score, extra_info, submitted_at, hidden, scores.flagged
+FROM scores
+JOIN players ON scores.player_id = players.player_id
+WHERE board_id = ? AND (? OR scores.player_id = ?) AND (? OR scores.flagged <> 0)
+ORDER BY score_id ASC
+LIMIT ? OFFSET ?
+        ",
+    )
+    .bind(board_id)
+    .bind(query.player_id.is_none())
+    .bind(query.player_id.unwrap_or_default())
+    .bind(!query.flagged)
+    .bind(query.limit.map_or(i64::MAX, i64::from))
+    .bind(i64::from(query.offset.unwrap_or(0)))
+    .try_map(|row: AnyRow| decode_record(&row))
+    .fetch_all(database)
+    .await?;
+    Ok(records)
+}
+
+/// Fetches the score of the board by id.
+async fn fetch_record(database: &DatabasePool, board_id: Id, score_id: Id) -> Result<ScoreRecord> {
+    sqlx::query(
+        "
+SELECT score_id, scores.player_id, players.name AS player_name,
+    score, extra_info, submitted_at, hidden, scores.flagged
+FROM scores
+JOIN players ON scores.player_id = players.player_id
+WHERE board_id = ? AND score_id = ?
+        ",
+    )
+    .bind(board_id)
+    .bind(score_id)
+    .try_map(|row: AnyRow| decode_record(&row))
+    .fetch_optional(database)
+    .await?
+    .ok_or(RequestError::NoSuchScore(score_id))
+}
+
+pub(super) async fn update_score(
+    Path((board_name, score_id)): Path<(String, Id)>,
+    State(database): State<Arc<DatabasePool>>,
+    api_key: Option<ApiKey>,
+    Json(update): Json<ScoreUpdate>,
+) -> Result<Json<ScoreRecord>> {
+    let (board, auth) = check_board(Path(board_name), State(database.clone()), api_key).await?;
+    check_auth(auth, AuthorityLevel::Admin)?;
+
+    let mut record = fetch_record(&database, board.id, score_id).await?;
+    if let Some(hidden) = update.hidden {
+        record.hidden = hidden;
+    }
+    if let Some(flagged) = update.flagged {
+        record.flagged = flagged;
+    }
+
+    sqlx::query("UPDATE scores SET hidden = ?, flagged = ? WHERE score_id = ?")
+        .bind(i32::from(record.hidden))
+        .bind(i32::from(record.flagged))
+        .bind(score_id)
+        .execute(&*database)
+        .await?;
+
+    Ok(Json(record))
+}
+
+pub(super) async fn delete_score(
+    Path((board_name, score_id)): Path<(String, Id)>,
+    State(database): State<Arc<DatabasePool>>,
+    api_key: Option<ApiKey>,
+) -> Result<()> {
+    let (board, auth) = check_board(Path(board_name), State(database.clone()), api_key).await?;
+    check_auth(auth, AuthorityLevel::Admin)?;
+
+    fetch_record(&database, board.id, score_id).await?;
+    sqlx::query("DELETE FROM scores WHERE score_id = ?")
+        .bind(score_id)
+        .execute(&*database)
+        .await?;
+
+    Ok(())
+}
+
+/// Deletes all scores of the player on the board.
+pub(super) async fn wipe_player_scores(
+    Path((board_name, player_id)): Path<(String, Id)>,
+    State(database): State<Arc<DatabasePool>>,
+    api_key: Option<ApiKey>,
+) -> Result<()> {
+    let (board, auth) = check_board(Path(board_name), State(database.clone()), api_key).await?;
+    check_auth(auth, AuthorityLevel::Admin)?;
+
+    sqlx::query("DELETE FROM scores WHERE board_id = ? AND player_id = ?")
+        .bind(board.id)
+        .bind(player_id)
+        .execute(&*database)
+        .await?;
+
+    Ok(())
+}
+
+/// Checks whether the player is banned from submitting to the board.
+pub(super) async fn is_banned(
+    database: &DatabasePool,
+    board_id: Id,
+    player_id: Id,
+) -> Result<bool> {
+    let bans =
+        sqlx::query("SELECT COUNT(*) AS bans FROM board_bans WHERE board_id = ? AND player_id = ?")
+            .bind(board_id)
+            .bind(player_id)
+            .try_map(|row: AnyRow| row.try_get::<i64, _>("bans"))
+            .fetch_one(database)
+            .await?;
+    Ok(bans > 0)
+}
+
+pub(super) async fn list_bans(
+    Path(board_name): Path<String>,
+    State(database): State<Arc<DatabasePool>>,
+    api_key: Option<ApiKey>,
+) -> Result<Json<Vec<BanInfo>>> {
+    let (board, auth) = check_board(Path(board_name), State(database.clone()), api_key).await?;
+    check_auth(auth, AuthorityLevel::Admin)?;
+
+    let bans = sqlx::query(
+        "
+SELECT board_bans.player_id, players.name AS player_name, banned_at
+FROM board_bans
+JOIN players ON board_bans.player_id = players.player_id
+WHERE board_id = ?
+ORDER BY banned_at, board_bans.player_id
+        ",
+    )
+    .bind(board.id)
+    .try_map(|row: AnyRow| {
+        Ok(BanInfo {
+            player_id: row.try_get("player_id")?,
+            player_name: row.try_get("player_name")?,
+            banned_at: row.try_get("banned_at")?,
+        })
+    })
+    .fetch_all(&*database)
+    .await?;
+
+    Ok(Json(bans))
+}
+
+/// Bans the player from submitting to the board.
+/// Existing scores are kept, wipe them separately if needed.
+pub(super) async fn ban_player(
+    Path((board_name, player_id)): Path<(String, Id)>,
+    State(database): State<Arc<DatabasePool>>,
+    api_key: Option<ApiKey>,
+) -> Result<()> {
+    let (board, auth) = check_board(Path(board_name), State(database.clone()), api_key).await?;
+    check_auth(auth, AuthorityLevel::Admin)?;
+
+    insert_ban(&database, board.id, player_id).await
+}
+
+/// Bans the player from the board if not banned already.
+pub(crate) async fn insert_ban(database: &DatabasePool, board_id: Id, player_id: Id) -> Result<()> {
+    let players = sqlx::query("SELECT COUNT(*) AS players FROM players WHERE player_id = ?")
+        .bind(player_id)
+        .try_map(|row: AnyRow| row.try_get::<i64, _>("players"))
+        .fetch_one(database)
+        .await?;
+    if players == 0 {
+        return Err(RequestError::NoSuchPlayer(player_id));
+    }
+
+    let result =
+        sqlx::query("INSERT INTO board_bans (board_id, player_id, banned_at) VALUES (?, ?, ?)")
+            .bind(board_id)
+            .bind(player_id)
+            .bind(Utc::now().timestamp())
+            .execute(database)
+            .await;
+    match result {
+        Ok(_) => Ok(()),
+        // Banned already
+        Err(error) if is_unique_violation(&error) => Ok(()),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// Lifts the ban of the player from the board.
+pub(super) async fn unban_player(
+    Path((board_name, player_id)): Path<(String, Id)>,
+    State(database): State<Arc<DatabasePool>>,
+    api_key: Option<ApiKey>,
+) -> Result<()> {
+    let (board, auth) = check_board(Path(board_name), State(database.clone()), api_key).await?;
+    check_auth(auth, AuthorityLevel::Admin)?;
+
+    let deleted = sqlx::query("DELETE FROM board_bans WHERE board_id = ? AND player_id = ?")
+        .bind(board.id)
+        .bind(player_id)
+        .execute(&*database)
+        .await?
+        .rows_affected();
+    if deleted == 0 {
+        return Err(RequestError::NoSuchPlayer(player_id));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,235 @@
+use super::*;
+
+use nertboard_core::{NameChange, Player};
+
+/// Checks the name against the configured rules
+/// and returns it normalized and filtered, as it is going to be stored.
+/// The player being renamed, if any, is ignored when checking uniqueness.
+pub(super) async fn validate_name(
+    database: &DatabasePool,
+    rules: &NameRules,
+    filter: &ContentFilter,
+    name: &str,
+    player_id: Option<Id>,
+) -> Result<Filtered> {
+    let name = rules
+        .validate(name)
+        .map_err(RequestError::InvalidPlayerName)?;
+    let name = filter.apply("player name", &name)?;
+
+    if rules.unique {
+        let taken = sqlx::query(
+            "SELECT COUNT(*) AS taken FROM players WHERE LOWER(name) = LOWER(?) AND player_id <> ?",
+        )
+        .bind(&name.text)
+        .bind(player_id.unwrap_or(-1))
+        .try_map(|row: AnyRow| row.try_get::<i64, _>("taken"))
+        .fetch_one(database)
+        .await?;
+        if taken > 0 {
+            return Err(RequestError::InvalidPlayerName(
+                "name is already taken".to_owned(),
+            ));
+        }
+    }
+
+    Ok(name)
+}
+
+pub(super) async fn create_player(
+    State(database): State<Arc<DatabasePool>>,
+    State(names): State<Arc<NameRules>>,
+    State(filter): State<Arc<ContentFilter>>,
+    State(key_lengths): State<Arc<KeyLengths>>,
+    Json(player_name): Json<String>,
+) -> Result<Json<Player>> {
+    let player_name = validate_name(&database, &names, &filter, &player_name, None).await?;
+
+    // Generate random secrets
+    let key = StringKey::generate(key_lengths.player);
+    let recovery_code = StringKey::generate(key_lengths.recovery);
+
+    let id = sqlx::query(
+        "
+INSERT INTO players (player_key, recovery_hash, name, flagged) VALUES (?, ?, ?, ?)
+RETURNING player_id
+        ",
+    )
+    .bind(HashedKey::hash(key.inner()).as_str())
+    .bind(HashedKey::hash(recovery_code.inner()).as_str())
+    .bind(&player_name.text)
+    .bind(i32::from(player_name.flagged))
+    .try_map(|row: AnyRow| row.try_get::<Id, _>("player_id"))
+    .fetch_one(&*database)
+    .await?;
+
+    Ok(Json(Player {
+        id,
+        key: key.inner().to_owned(),
+        name: player_name.text,
+        recovery_code: Some(recovery_code.inner().to_owned()),
+    }))
+}
+
+/// Checks the key of the player and returns their name.
+pub(super) async fn check_player(
+    database: &DatabasePool,
+    player_id: Id,
+    player_key: &str,
+) -> Result<String> {
+    let player = sqlx::query("SELECT player_key, name FROM players WHERE player_id = ?")
+        .bind(player_id)
+        .try_map(|row: AnyRow| {
+            Ok((
+                HashedKey::from_stored(row.try_get::<String, _>("player_key")?),
+                row.try_get::<String, _>("name")?,
+            ))
+        })
+        .fetch_optional(database)
+        .await?;
+
+    match player {
+        Some((real_key, name)) if real_key.verify(player_key) => Ok(name),
+        // Unknown player or invalid key
+        _ => Err(RequestError::InvalidPlayer),
+    }
+}
+
+/// Replaces the key of the player with a new one.
+pub(super) async fn regenerate_key(
+    Path(player_id): Path<Id>,
+    State(database): State<Arc<DatabasePool>>,
+    State(key_lengths): State<Arc<KeyLengths>>,
+    State(limiter): State<Arc<RateLimiter>>,
+    player_key: PlayerKey,
+) -> Result<Json<Player>> {
+    let name = check_player(&database, player_id, &player_key.0).await?;
+    limiter.limit_player(Budget::Player, player_id)?;
+
+    let key = StringKey::generate(key_lengths.player);
+    sqlx::query("UPDATE players SET player_key = ? WHERE player_id = ?")
+        .bind(HashedKey::hash(key.inner()).as_str())
+        .bind(player_id)
+        .execute(&*database)
+        .await?;
+
+    Ok(Json(Player {
+        id: player_id,
+        key: key.inner().to_owned(),
+        name,
+        recovery_code: None,
+    }))
+}
+
+/// Reclaims a player with a lost key using their recovery code.
+/// Both the key and the recovery code are replaced,
+/// so a recovery code can only be used once.
+pub(super) async fn recover_player(
+    Path(player_id): Path<Id>,
+    State(database): State<Arc<DatabasePool>>,
+    State(key_lengths): State<Arc<KeyLengths>>,
+    Json(recovery_code): Json<String>,
+) -> Result<Json<Player>> {
+    let player = sqlx::query("SELECT recovery_hash, name FROM players WHERE player_id = ?")
+        .bind(player_id)
+        .try_map(|row: AnyRow| {
+            Ok((
+                decode_optional::<String>(&row, "recovery_hash")?.map(HashedKey::from_stored),
+                row.try_get::<String, _>("name")?,
+            ))
+        })
+        .fetch_optional(&*database)
+        .await?;
+
+    let name = match player {
+        Some((Some(hash), name)) if hash.verify(&recovery_code) => name,
+        // Unknown player, no recovery code, or invalid code
+        _ => return Err(RequestError::InvalidPlayer),
+    };
+
+    let key = StringKey::generate(key_lengths.player);
+    let recovery_code = StringKey::generate(key_lengths.recovery);
+    sqlx::query("UPDATE players SET player_key = ?, recovery_hash = ? WHERE player_id = ?")
+        .bind(HashedKey::hash(key.inner()).as_str())
+        .bind(HashedKey::hash(recovery_code.inner()).as_str())
+        .bind(player_id)
+        .execute(&*database)
+        .await?;
+
+    Ok(Json(Player {
+        id: player_id,
+        key: key.inner().to_owned(),
+        name,
+        recovery_code: Some(recovery_code.inner().to_owned()),
+    }))
+}
+
+/// Changes the name of the player and records the change in the history.
+pub(super) async fn rename_player(
+    Path(player_id): Path<Id>,
+    State(database): State<Arc<DatabasePool>>,
+    State(names): State<Arc<NameRules>>,
+    State(filter): State<Arc<ContentFilter>>,
+    State(limiter): State<Arc<RateLimiter>>,
+    player_key: PlayerKey,
+    Json(new_name): Json<String>,
+) -> Result<Json<String>> {
+    let old_name = check_player(&database, player_id, &player_key.0).await?;
+    limiter.limit_player(Budget::Player, player_id)?;
+    let Filtered {
+        text: new_name,
+        flagged,
+    } = validate_name(&database, &names, &filter, &new_name, Some(player_id)).await?;
+    if new_name == old_name {
+        return Ok(Json(new_name));
+    }
+
+    let mut transaction = database.begin().await?;
+    sqlx::query("UPDATE players SET name = ?, flagged = ? WHERE player_id = ?")
+        .bind(&new_name)
+        .bind(i32::from(flagged))
+        .bind(player_id)
+        .execute(&mut *transaction)
+        .await?;
+    sqlx::query(
+        "INSERT INTO player_names (player_id, old_name, new_name, renamed_at) VALUES (?, ?, ?, ?)",
+    )
+    .bind(player_id)
+    .bind(&old_name)
+    .bind(&new_name)
+    .bind(Utc::now().timestamp())
+    .execute(&mut *transaction)
+    .await?;
+    transaction.commit().await?;
+
+    Ok(Json(new_name))
+}
+
+/// Lists the past names of the player, oldest first.
+pub(super) async fn name_history(
+    Path(player_id): Path<Id>,
+    State(database): State<Arc<DatabasePool>>,
+    player_key: PlayerKey,
+) -> Result<Json<Vec<NameChange>>> {
+    check_player(&database, player_id, &player_key.0).await?;
+
+    let history = sqlx::query(
+        "
+SELECT old_name, new_name, renamed_at FROM player_names
+WHERE player_id = ?
+ORDER BY renamed_at, rename_id
+        ",
+    )
+    .bind(player_id)
+    .try_map(|row: AnyRow| {
+        Ok(NameChange {
+            old_name: row.try_get("old_name")?,
+            new_name: row.try_get("new_name")?,
+            renamed_at: row.try_get("renamed_at")?,
+        })
+    })
+    .fetch_all(&*database)
+    .await?;
+
+    Ok(Json(history))
+}
@@ -0,0 +1,195 @@
+use super::*;
+
+use crate::config::RateLimits;
+
+use axum::{
+    extract::{ConnectInfo, MatchedPath, Request},
+    http::{HeaderMap, Method},
+    middleware::Next,
+    response::Response,
+};
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Period over which the limits are defined.
+const WINDOW: Duration = Duration::from_secs(60);
+
+/// Kind of requests that share a limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(super) enum Budget {
+    Player,
+    Submit,
+    Read,
+}
+
+/// Identity of the client a limit applies to.
+/// Only identities that have been verified are tracked,
+/// so that nobody can spend the budget of someone else.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Client {
+    Address(IpAddr),
+    Key(Id),
+    Player(Id),
+}
+
+/// Token bucket refilled continuously over the window.
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+struct Buckets {
+    buckets: HashMap<(Budget, Client), Bucket>,
+    pruned: Instant,
+}
+
+/// Keeps track of the requests made by every client in memory.
+pub(super) struct RateLimiter {
+    limits: RateLimits,
+    trusted_proxies: Vec<IpAddr>,
+    buckets: Mutex<Buckets>,
+}
+
+impl RateLimiter {
+    pub fn new(limits: RateLimits, trusted_proxies: Vec<IpAddr>) -> Self {
+        Self {
+            limits,
+            trusted_proxies,
+            buckets: Mutex::new(Buckets {
+                buckets: HashMap::new(),
+                pruned: Instant::now(),
+            }),
+        }
+    }
+
+    /// Takes a request out of the budget of the player.
+    /// Must only be called once the player has been authorized.
+    pub fn limit_player(&self, budget: Budget, player_id: Id) -> Result<()> {
+        self.check(budget, Client::Player(player_id), Instant::now())
+            .map_err(RequestError::TooManyRequests)
+    }
+
+    /// Finds the address of the client that made the request through the peer.
+    /// Proxies append the address they received the request from to `X-Forwarded-For`,
+    /// so the client is the last address there not belonging to a trusted proxy.
+    fn client_address(&self, peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+        let forwarded: Vec<&str> = headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .collect();
+        let mut address = peer;
+        for hop in forwarded.into_iter().rev() {
+            if !self.trusted_proxies.contains(&address) {
+                break;
+            }
+            match hop.trim().parse() {
+                Ok(hop) => address = hop,
+                Err(_) => break,
+            }
+        }
+        address
+    }
+
+    fn limit(&self, budget: Budget) -> Option<u32> {
+        match budget {
+            Budget::Player => self.limits.player,
+            Budget::Submit => self.limits.submit,
+            Budget::Read => self.limits.read,
+        }
+    }
+
+    /// Takes a request out of the budget of the client.
+    /// If it has run out, the number of seconds to wait is returned.
+    fn check(&self, budget: Budget, client: Client, now: Instant) -> Result<(), u64> {
+        let Some(limit) = self.limit(budget) else {
+            return Ok(());
+        };
+        let capacity = f64::from(limit);
+        let refill_rate = capacity / WINDOW.as_secs_f64();
+
+        let mut buckets = self.buckets.lock().unwrap();
+        if now.saturating_duration_since(buckets.pruned) >= WINDOW {
+            // Buckets idle for the whole window are full again anyway
+            buckets
+                .buckets
+                .retain(|_, bucket| now.saturating_duration_since(bucket.updated) < WINDOW);
+            buckets.pruned = now;
+        }
+
+        let bucket = buckets.buckets.entry((budget, client)).or_insert(Bucket {
+            tokens: capacity,
+            updated: now,
+        });
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * refill_rate).min(capacity);
+        bucket.updated = now;
+        if bucket.tokens < 1.0 {
+            return Err(((1.0 - bucket.tokens) / refill_rate).ceil() as u64);
+        }
+        bucket.tokens -= 1.0;
+        Ok(())
+    }
+}
+
+/// Budget of the request, to take requests out of the budget of the api key
+/// once the handler has verified the key.
+#[derive(Clone)]
+pub(crate) struct KeyBudget {
+    limiter: Arc<RateLimiter>,
+    budget: Budget,
+}
+
+impl KeyBudget {
+    pub(super) fn limit_key(&self, key_id: Id) -> Result<()> {
+        self.limiter
+            .check(self.budget, Client::Key(key_id), Instant::now())
+            .map_err(RequestError::TooManyRequests)
+    }
+}
+
+/// Picks the budget of the request by its route.
+/// Requests that are not limited return `None`.
+fn request_budget(method: &Method, route: &str) -> Option<Budget> {
+    if *method == Method::POST && route.starts_with("/player/") {
+        Some(Budget::Player)
+    } else if *method == Method::POST && route == "/board/:board_name" {
+        Some(Budget::Submit)
+    } else if *method == Method::GET {
+        Some(Budget::Read)
+    } else {
+        None
+    }
+}
+
+/// Rejects the request if its address has exceeded the limit for its kind.
+/// Api keys and players are limited separately by the handlers once they are verified.
+pub(super) async fn limit_requests(
+    State(limiter): State<Arc<RateLimiter>>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response> {
+    let budget = request
+        .extensions()
+        .get::<MatchedPath>()
+        .and_then(|route| request_budget(request.method(), route.as_str()));
+    let Some(budget) = budget else {
+        return Ok(next.run(request).await);
+    };
+
+    if let Some(ConnectInfo(peer)) = request.extensions().get::<ConnectInfo<SocketAddr>>() {
+        let address = limiter.client_address(peer.ip(), request.headers());
+        limiter
+            .check(budget, Client::Address(address), Instant::now())
+            .map_err(RequestError::TooManyRequests)?;
+    }
+    request
+        .extensions_mut()
+        .insert(KeyBudget { limiter, budget });
+    Ok(next.run(request).await)
+}
@@ -0,0 +1,156 @@
+use super::*;
+
+use axum::http::request::Parts;
+use nertboard_core::{
+    signing::{self, MAX_NONCE_LENGTH, NONCE_HEADER, SIGNATURE_HEADER, TIMESTAMP_HEADER},
+    ScoreEntry, SigningSecret,
+};
+
+/// How far the timestamp of a signed submission may be from the server time, in seconds.
+/// Nonces are remembered for at least this long to reject replays.
+const MAX_SIGNATURE_AGE: i64 = 300;
+
+/// Signature headers of a submission.
+pub(super) struct SubmissionSignature {
+    signature: String,
+    timestamp: i64,
+    nonce: String,
+}
+
+#[axum::async_trait]
+impl<S> axum::extract::FromRequestParts<S> for SubmissionSignature {
+    type Rejection = RequestError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let header = |name: &str| {
+            parts
+                .headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_owned)
+        };
+        let (Some(signature), Some(timestamp), Some(nonce)) = (
+            header(SIGNATURE_HEADER),
+            header(TIMESTAMP_HEADER),
+            header(NONCE_HEADER),
+        ) else {
+            return Err(RequestError::InvalidHeader("signature headers missing"));
+        };
+        let Ok(timestamp) = timestamp.parse() else {
+            return Err(RequestError::InvalidHeader(
+                "signature timestamp is invalid",
+            ));
+        };
+        Ok(Self {
+            signature,
+            timestamp,
+            nonce,
+        })
+    }
+}
+
+/// Generates a new signing secret for the board and requires signatures from now on.
+/// Submissions signed with the old secret stop being accepted.
+pub(super) async fn enable_signing(
+    Path(board_name): Path<String>,
+    State(database): State<Arc<DatabasePool>>,
+    State(key_lengths): State<Arc<KeyLengths>>,
+    api_key: Option<ApiKey>,
+) -> Result<Json<SigningSecret>> {
+    let (board, auth) = check_board(Path(board_name), State(database.clone()), api_key).await?;
+    check_auth(auth, AuthorityLevel::Admin)?;
+
+    // The secret is stored as is, since it is needed to verify signatures
+    let secret = StringKey::generate(key_lengths.signing).inner().to_owned();
+    sqlx::query("UPDATE boards SET signing_secret = ? WHERE board_id = ?")
+        .bind(&secret)
+        .bind(board.id)
+        .execute(&*database)
+        .await?;
+
+    Ok(Json(SigningSecret { secret }))
+}
+
+pub(super) async fn disable_signing(
+    Path(board_name): Path<String>,
+    State(database): State<Arc<DatabasePool>>,
+    api_key: Option<ApiKey>,
+) -> Result<()> {
+    let (board, auth) = check_board(Path(board_name), State(database.clone()), api_key).await?;
+    check_auth(auth, AuthorityLevel::Admin)?;
+
+    sqlx::query("UPDATE boards SET signing_secret = NULL WHERE board_id = ?")
+        .bind(board.id)
+        .execute(&*database)
+        .await?;
+
+    Ok(())
+}
+
+/// Verifies the signature of the submission if the board requires one,
+/// and marks the nonce as used.
+pub(super) async fn verify_submission(
+    database: &DatabasePool,
+    board: &Board,
+    board_name: &str,
+    player_id: Id,
+    score: &ScoreEntry,
+    signature: Option<SubmissionSignature>,
+    now: i64,
+) -> Result<()> {
+    let secret = sqlx::query("SELECT signing_secret FROM boards WHERE board_id = ?")
+        .bind(board.id)
+        .try_map(|row: AnyRow| decode_optional::<String>(&row, "signing_secret"))
+        .fetch_one(database)
+        .await?;
+    let Some(secret) = secret else {
+        // Signing is not enabled
+        return Ok(());
+    };
+
+    let Some(signature) = signature else {
+        return Err(RequestError::InvalidSignature("signature missing"));
+    };
+    if (signature.timestamp - now).abs() > MAX_SIGNATURE_AGE {
+        return Err(RequestError::InvalidSignature("timestamp is too far off"));
+    }
+    if signature.nonce.is_empty() || signature.nonce.len() > MAX_NONCE_LENGTH {
+        return Err(RequestError::InvalidSignature("nonce is invalid"));
+    }
+
+    let message = signing::submission_message(
+        board_name,
+        player_id,
+        score,
+        signature.timestamp,
+        &signature.nonce,
+    );
+    if !signing::verify(&secret, &message, &signature.signature) {
+        return Err(RequestError::InvalidSignature("signature does not match"));
+    }
+
+    // Reject replays
+    sqlx::query("DELETE FROM submission_nonces WHERE used_at < ?")
+        .bind(now - 2 * MAX_SIGNATURE_AGE)
+        .execute(database)
+        .await?;
+    // The primary key rejects a nonce that is already used
+    let inserted =
+        sqlx::query("INSERT INTO submission_nonces (board_id, nonce, used_at) VALUES (?, ?, ?)")
+            .bind(board.id)
+            .bind(&signature.nonce)
+            .bind(now)
+            .execute(database)
+            .await;
+    match inserted {
+        Ok(_) => {}
+        Err(error) if is_unique_violation(&error) => {
+            return Err(RequestError::InvalidSignature(
+                "nonce has already been used",
+            ));
+        }
+        Err(error) => return Err(error.into()),
+    }
+
+    Ok(())
+}
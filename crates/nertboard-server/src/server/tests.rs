@@ -1,17 +1,27 @@
 use super::*;
 
+use crate::{
+    config::{FilterConfig, Operator, RateLimits},
+    filter::FilterMode,
+};
+
+use axum::routing::RouterIntoService;
 use axum::{
     body::Body,
     http::{request::Builder, Request, Response, StatusCode},
 };
 use color_eyre::Result;
 use http_body_util::BodyExt;
-use nertboard_core::Player;
+use nertboard_core::{
+    BoardInfo, BoardInfoUpdate, ErrorCode, ErrorResponse, Player, Score, ScoreOrder, ScorePolicy,
+    ScoreValidation, SubmissionLimit,
+};
+use rand::Rng;
 use serde::{de::DeserializeOwned, Serialize};
 use tower::{util::ServiceExt, Service};
 
 async fn test_database() -> Result<DatabasePool> {
-    crate::setup::setup().context("when setting up the environment")?;
+    crate::setup::setup_test();
 
     let pool = sqlx::any::AnyPoolOptions::new()
         .min_connections(1)
@@ -27,12 +37,65 @@ async fn test_database() -> Result<DatabasePool> {
     Ok(pool)
 }
 
+/// Database in a temporary file, unlike the in-memory one it allows concurrent connections.
+/// The file is removed when the returned guard is dropped.
+async fn test_file_database() -> Result<(DatabasePool, TempFile)> {
+    crate::setup::setup_test();
+
+    let path = std::env::temp_dir().join(format!(
+        "nertboard_test_{}.db",
+        rand::thread_rng().gen::<u32>()
+    ));
+    let file = TempFile(path);
+    let pool = sqlx::any::AnyPoolOptions::new()
+        .max_connections(8)
+        .connect(&format!("sqlite://{}?mode=rwc", file.0.display()))
+        .await
+        .context("when connecting to the database file")?;
+
+    crate::database::init_database(&pool)
+        .await
+        .context("when initializing the test database")?;
+
+    Ok((pool, file))
+}
+
+struct TempFile(std::path::PathBuf);
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
 async fn test_app() -> Result<Router> {
-    Ok(app(Arc::new(
-        test_database()
-            .await
-            .context("when setting up a test database")?,
-    )))
+    test_app_with(test_config()).await
+}
+
+/// Default config that lets anyone create boards.
+fn test_config() -> Config {
+    Config {
+        board_creation: BoardCreation {
+            open: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+async fn test_app_with(config: Config) -> Result<Router> {
+    let (app, _) = test_app_with_database(config).await?;
+    Ok(app)
+}
+
+/// Also returns the database, for tests that need to set up data
+/// that cannot be created through the api.
+async fn test_app_with_database(config: Config) -> Result<(Router, Arc<DatabasePool>)> {
+    let database = test_database()
+        .await
+        .context("when setting up a test database")?;
+    let database = Arc::new(database);
+    Ok((app(AppState::new(database.clone(), config)?), database))
 }
 
 fn request_json<T: Serialize>(request: Builder, body: &T) -> Result<Request<Body>> {
@@ -57,6 +120,94 @@ async fn response_json<T: DeserializeOwned>(response: Response<Body>) -> Result<
     Ok(body)
 }
 
+async fn send(app: &mut RouterIntoService<Body>, request: Request<Body>) -> Result<Response<Body>> {
+    let response = app.ready().await?.call(request).await?;
+    println!("{:?}", response);
+    Ok(response)
+}
+
+async fn create_board(app: &mut RouterIntoService<Body>, name: &str) -> Result<BoardKeys> {
+    create_board_with(app, name, BoardSettings::default()).await
+}
+
+async fn create_board_with(
+    app: &mut RouterIntoService<Body>,
+    name: &str,
+    settings: BoardSettings,
+) -> Result<BoardKeys> {
+    let board = BoardCreate {
+        name: name.to_string(),
+        settings,
+    };
+    let response = send(app, request_json(Request::post("/board/create"), &board)?).await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    response_json(response).await
+}
+
+async fn create_player(app: &mut RouterIntoService<Body>, name: &str) -> Result<Player> {
+    let response = send(app, request_json(Request::post("/player/create"), &name)?).await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    response_json(response).await
+}
+
+async fn rename_player(
+    app: &mut RouterIntoService<Body>,
+    player: &Player,
+    name: &str,
+) -> Result<Response<Body>> {
+    send(
+        app,
+        request_json(
+            Request::post(format!("/player/{}/rename", player.id))
+                .header("player-key", &player.key),
+            &name,
+        )?,
+    )
+    .await
+}
+
+async fn submit_score(
+    app: &mut RouterIntoService<Body>,
+    board_name: &str,
+    api_key: &str,
+    player: &Player,
+    score: Score,
+) -> Result<Response<Body>> {
+    let entry = nertboard_core::ScoreEntry {
+        player: player.name.clone(),
+        score,
+        extra_info: None,
+        submitted_at: None,
+    };
+    send(
+        app,
+        request_json(
+            Request::post(format!("/board/{}?player_id={}", board_name, player.id))
+                .header("api-key", api_key)
+                .header("player-key", &player.key),
+            &entry,
+        )?,
+    )
+    .await
+}
+
+async fn fetch_scores(
+    app: &mut RouterIntoService<Body>,
+    board_name: &str,
+    api_key: &str,
+    query: &str,
+) -> Result<nertboard_core::ScoresPage> {
+    let response = send(
+        app,
+        Request::get(format!("/board/{}?{}", board_name, query))
+            .header("api-key", api_key)
+            .body(Body::empty())?,
+    )
+    .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    response_json(response).await
+}
+
 #[tokio::test]
 async fn test_e2e() -> Result<()> {
     let mut app = test_app().await?.into_service();
@@ -65,7 +216,13 @@ async fn test_e2e() -> Result<()> {
     let response = app
         .ready()
         .await?
-        .call(request_json(Request::post("/board/create"), &"test-table")?)
+        .call(request_json(
+            Request::post("/board/create"),
+            &BoardCreate {
+                name: "test-table".to_string(),
+                settings: BoardSettings::default(),
+            },
+        )?)
         .await?;
 
     println!("{:?}", response);
@@ -89,11 +246,13 @@ async fn test_e2e() -> Result<()> {
             player: "nertsal".to_string(),
             score: 10,
             extra_info: None,
+            submitted_at: None,
         },
         nertboard_core::ScoreEntry {
-            player: "nert".to_string(), // Change name
+            player: "nert".to_string(), // Ignored, the player is not renamed
             score: 5,
             extra_info: Some("very cool".to_string()),
+            submitted_at: None,
         },
     ];
 
@@ -103,7 +262,7 @@ async fn test_e2e() -> Result<()> {
         .await?
         .call(request_json(
             Request::post(format!("/board/test-table?player_id={}", player.id))
-                .header("api-key", keys.submit.inner())
+                .header("api-key", &keys.submit)
                 .header("player-key", &player.key),
             &scores[0],
         )?)
@@ -118,7 +277,7 @@ async fn test_e2e() -> Result<()> {
         .await?
         .call(request_json(
             Request::post(format!("/board/test-table?player_id={}", player.id))
-                .header("api-key", keys.submit.inner())
+                .header("api-key", &keys.submit)
                 .header("player-key", &player.key),
             &scores[1],
         )?)
@@ -133,19 +292,32 @@ async fn test_e2e() -> Result<()> {
         .await?
         .call(
             Request::get("/board/test-table")
-                .header("api-key", keys.read.inner())
+                .header("api-key", &keys.read)
                 .body(Body::empty())?,
         )
         .await?;
 
     println!("{:?}", response);
     assert_eq!(response.status(), StatusCode::OK);
-    let returned_scores: Vec<nertboard_core::ScoreEntry> = response_json(response).await?;
-    // Update name
+    let returned_scores: nertboard_core::ScoresPage = response_json(response).await?;
+    // Names come from the player
     let new_scores: Vec<_> = scores
         .into_iter()
         .map(|entry| nertboard_core::ScoreEntry {
-            player: "nert".to_string(),
+            player: "nertsal".to_string(),
+            ..entry
+        })
+        .collect();
+    assert_eq!(returned_scores.total, 2);
+    assert!(returned_scores
+        .scores
+        .iter()
+        .all(|entry| entry.submitted_at.is_some()));
+    let returned_scores: Vec<_> = returned_scores
+        .scores
+        .into_iter()
+        .map(|entry| nertboard_core::ScoreEntry {
+            submitted_at: None,
             ..entry
         })
         .collect();
@@ -153,3 +325,1597 @@ async fn test_e2e() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn test_error_response() -> Result<()> {
+    let mut app = test_app().await?.into_service();
+
+    // Missing board
+    let response = app
+        .ready()
+        .await?
+        .call(Request::get("/board/missing").body(Body::empty())?)
+        .await?;
+
+    println!("{:?}", response);
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let error: ErrorResponse = response_json(response).await?;
+    assert_eq!(error.code, ErrorCode::NoSuchBoard);
+
+    // Create board
+    let response = app
+        .ready()
+        .await?
+        .call(request_json(
+            Request::post("/board/create"),
+            &BoardCreate {
+                name: "test-table".to_string(),
+                settings: BoardSettings::default(),
+            },
+        )?)
+        .await?;
+    let keys: BoardKeys = response_json(response).await?;
+
+    // Delete with a read key
+    let response = app
+        .ready()
+        .await?
+        .call(
+            Request::delete("/board/test-table")
+                .header("api-key", &keys.read)
+                .body(Body::empty())?,
+        )
+        .await?;
+
+    println!("{:?}", response);
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let error: ErrorResponse = response_json(response).await?;
+    assert_eq!(error.code, ErrorCode::Forbidden);
+
+    // Submit without a player key
+    let response = app
+        .ready()
+        .await?
+        .call(request_json(
+            Request::post("/board/test-table?player_id=1").header("api-key", &keys.submit),
+            &nertboard_core::ScoreEntry {
+                player: "nertsal".to_string(),
+                score: 10,
+                extra_info: None,
+                submitted_at: None,
+            },
+        )?)
+        .await?;
+
+    println!("{:?}", response);
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let error: ErrorResponse = response_json(response).await?;
+    assert_eq!(error.code, ErrorCode::InvalidRequest);
+
+    // Invalid body
+    let response = app
+        .ready()
+        .await?
+        .call(request_json(Request::post("/player/create"), &10)?)
+        .await?;
+
+    println!("{:?}", response);
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let error: ErrorResponse = response_json(response).await?;
+    assert_eq!(error.code, ErrorCode::InvalidRequest);
+    assert!(error.details.is_some());
+
+    // Unknown route
+    let response = app
+        .ready()
+        .await?
+        .call(Request::get("/unknown").body(Body::empty())?)
+        .await?;
+
+    println!("{:?}", response);
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let error: ErrorResponse = response_json(response).await?;
+    assert_eq!(error.code, ErrorCode::NotFound);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_score_pagination() -> Result<()> {
+    let mut app = test_app().await?.into_service();
+
+    let keys = create_board(&mut app, "test-table").await?;
+    let player = create_player(&mut app, "nertsal").await?;
+    for score in [20, 10, 30, 0] {
+        let response = submit_score(&mut app, "test-table", &keys.submit, &player, score).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    let page = fetch_scores(&mut app, "test-table", &keys.read, "").await?;
+    assert_eq!(page.total, 4);
+    let scores: Vec<Score> = page.scores.iter().map(|entry| entry.score).collect();
+    assert_eq!(scores, vec![30, 20, 10, 0]);
+
+    let page = fetch_scores(
+        &mut app,
+        "test-table",
+        &keys.read,
+        "order=ascending&limit=2&offset=1",
+    )
+    .await?;
+    assert_eq!(page.total, 4);
+    let scores: Vec<Score> = page.scores.iter().map(|entry| entry.score).collect();
+    assert_eq!(scores, vec![10, 20]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_board_policy() -> Result<()> {
+    let mut app = test_app().await?.into_service();
+
+    let settings = BoardSettings {
+        order: ScoreOrder::Ascending,
+        policy: ScorePolicy::Best,
+        ..Default::default()
+    };
+    let best = create_board_with(&mut app, "best", settings).await?;
+    let settings = BoardSettings {
+        order: ScoreOrder::Descending,
+        policy: ScorePolicy::Latest,
+        ..Default::default()
+    };
+    let latest = create_board_with(&mut app, "latest", settings).await?;
+
+    let alice = create_player(&mut app, "alice").await?;
+    let bob = create_player(&mut app, "bob").await?;
+    for (player, score) in [(&alice, 30), (&alice, 20), (&alice, 25), (&bob, 10)] {
+        let response = submit_score(&mut app, "best", &best.submit, player, score).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = submit_score(&mut app, "latest", &latest.submit, player, score).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    let page = fetch_scores(&mut app, "best", &best.read, "").await?;
+    let scores: Vec<Score> = page.scores.iter().map(|entry| entry.score).collect();
+    assert_eq!(scores, vec![10, 20]);
+
+    let page = fetch_scores(&mut app, "latest", &latest.read, "").await?;
+    let scores: Vec<Score> = page.scores.iter().map(|entry| entry.score).collect();
+    assert_eq!(scores, vec![25, 10]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_player_rank() -> Result<()> {
+    let mut app = test_app().await?.into_service();
+
+    let keys = create_board(&mut app, "test-table").await?;
+    let mut players = Vec::new();
+    for (name, score) in [("a", 50), ("b", 40), ("c", 30), ("d", 20), ("e", 10)] {
+        let player = create_player(&mut app, name).await?;
+        let response = submit_score(&mut app, "test-table", &keys.submit, &player, score).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        players.push(player);
+    }
+
+    let response = send(
+        &mut app,
+        Request::get(format!(
+            "/board/test-table/player/{}?window=2",
+            players[1].id
+        ))
+        .header("api-key", &keys.read)
+        .body(Body::empty())?,
+    )
+    .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    let rank: nertboard_core::PlayerRank = response_json(response).await?;
+    assert_eq!(rank.rank, 2);
+    assert_eq!(rank.score.score, 40);
+    let above: Vec<Score> = rank.above.iter().map(|entry| entry.score).collect();
+    assert_eq!(above, vec![50]);
+    let below: Vec<Score> = rank.below.iter().map(|entry| entry.score).collect();
+    assert_eq!(below, vec![30, 20]);
+
+    // Player without scores
+    let player = create_player(&mut app, "f").await?;
+    let response = send(
+        &mut app,
+        Request::get(format!("/board/test-table/player/{}", player.id))
+            .header("api-key", &keys.read)
+            .body(Body::empty())?,
+    )
+    .await?;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let error: ErrorResponse = response_json(response).await?;
+    assert_eq!(error.code, ErrorCode::NoPlayerScore);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_submit_result() -> Result<()> {
+    let mut app = test_app().await?.into_service();
+
+    let settings = BoardSettings {
+        order: ScoreOrder::Descending,
+        policy: ScorePolicy::Best,
+        ..Default::default()
+    };
+    let keys = create_board_with(&mut app, "test-table", settings.clone()).await?;
+    let latest = BoardSettings {
+        policy: ScorePolicy::Latest,
+        ..settings
+    };
+    let latest_keys = create_board_with(&mut app, "latest-table", latest).await?;
+    let alice = create_player(&mut app, "alice").await?;
+    let bob = create_player(&mut app, "bob").await?;
+
+    let mut submit_to = async |board: &str,
+                               key: &str,
+                               player: &Player,
+                               score: Score|
+           -> Result<nertboard_core::SubmitResult> {
+        let response = submit_score(&mut app, board, key, player, score).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        response_json(response).await
+    };
+    let key = &keys.submit;
+    let mut submit =
+        async |player: &Player, score: Score| submit_to("test-table", key, player, score).await;
+
+    let result = submit(&alice, 10).await?;
+    assert_eq!(result.rank, 1);
+    assert_eq!(result.previous_best, None);
+    assert!(result.improved);
+
+    let result = submit(&bob, 20).await?;
+    assert_eq!(result.rank, 1);
+
+    let result = submit(&alice, 5).await?;
+    assert_eq!(result.rank, 2);
+    assert_eq!(result.previous_best, Some(10));
+    assert!(!result.improved);
+
+    let result = submit(&alice, 30).await?;
+    assert_eq!(result.rank, 1);
+    assert_eq!(result.previous_best, Some(10));
+    assert!(result.improved);
+
+    // The best score is compared even if the board shows the latest one
+    let key = &latest_keys.submit;
+    let mut submit =
+        async |player: &Player, score: Score| submit_to("latest-table", key, player, score).await;
+    let result = submit(&alice, 10).await?;
+    assert_eq!(result.previous_best, None);
+    assert!(result.improved);
+    let result = submit(&alice, 5).await?;
+    assert_eq!(result.previous_best, Some(10));
+    assert!(!result.improved);
+    let result = submit(&alice, 8).await?;
+    assert_eq!(result.previous_best, Some(10));
+    assert!(!result.improved);
+    let result = submit(&alice, 12).await?;
+    assert_eq!(result.previous_best, Some(10));
+    assert!(result.improved);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_score_time_range() -> Result<()> {
+    let mut app = test_app().await?.into_service();
+
+    let keys = create_board(&mut app, "test-table").await?;
+    let player = create_player(&mut app, "nertsal").await?;
+    let response = submit_score(&mut app, "test-table", &keys.submit, &player, 10).await?;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let now = Utc::now().timestamp();
+    let page = fetch_scores(&mut app, "test-table", &keys.read, "period=today").await?;
+    assert_eq!(page.total, 1);
+    let submitted_at = page.scores[0].submitted_at.unwrap();
+    assert!((now - 10..=now).contains(&submitted_at));
+
+    let query = format!("since={}", now + 100);
+    let page = fetch_scores(&mut app, "test-table", &keys.read, &query).await?;
+    assert_eq!(page.total, 0);
+    assert!(page.scores.is_empty());
+
+    let query = format!("since={}&until={}", now - 100, now + 100);
+    let page = fetch_scores(&mut app, "test-table", &keys.read, &query).await?;
+    assert_eq!(page.total, 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_policy_time_range() -> Result<()> {
+    let (app, database) = test_app_with_database(test_config()).await?;
+    let mut app = app.into_service();
+
+    let settings = BoardSettings {
+        order: ScoreOrder::Descending,
+        policy: ScorePolicy::Best,
+        ..Default::default()
+    };
+    let keys = create_board_with(&mut app, "test-table", settings).await?;
+    let alice = create_player(&mut app, "alice").await?;
+    let bob = create_player(&mut app, "bob").await?;
+
+    let mut submit = async |player: &Player, score: Score| -> Result<Id> {
+        let response = submit_score(&mut app, "test-table", &keys.submit, player, score).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        let result: nertboard_core::SubmitResult = response_json(response).await?;
+        Ok(result.score_id)
+    };
+    let old_best = submit(&alice, 50).await?;
+    submit(&alice, 20).await?;
+    submit(&alice, 30).await?;
+    submit(&bob, 40).await?;
+
+    // The all-time best of alice was submitted last month
+    let last_month = Utc::now().timestamp() - 40 * 24 * 60 * 60;
+    sqlx::query("UPDATE scores SET submitted_at = ? WHERE score_id = ?")
+        .bind(last_month)
+        .bind(old_best)
+        .execute(&*database)
+        .await?;
+
+    let entries = |page: nertboard_core::ScoresPage| -> Vec<(String, Score)> {
+        page.scores
+            .into_iter()
+            .map(|entry| (entry.player, entry.score))
+            .collect()
+    };
+
+    let page = fetch_scores(&mut app, "test-table", &keys.read, "").await?;
+    assert_eq!(page.total, 2);
+    assert_eq!(
+        entries(page),
+        vec![("alice".to_owned(), 50), ("bob".to_owned(), 40)]
+    );
+
+    // Only the best of this week is shown
+    let page = fetch_scores(&mut app, "test-table", &keys.read, "period=week").await?;
+    assert_eq!(page.total, 2);
+    assert_eq!(
+        entries(page),
+        vec![("bob".to_owned(), 40), ("alice".to_owned(), 30)]
+    );
+
+    let query = format!("until={}", last_month + 1);
+    let page = fetch_scores(&mut app, "test-table", &keys.read, &query).await?;
+    assert_eq!(entries(page), vec![("alice".to_owned(), 50)]);
+
+    Ok(())
+}
+
+#[test]
+fn test_period_start() {
+    // Wednesday, 2024-01-17 15:30:00 UTC
+    let now = DateTime::from_timestamp(1705505400, 0).unwrap();
+    assert_eq!(period_start(TimePeriod::Today, now), 1705449600); // 2024-01-17
+    assert_eq!(period_start(TimePeriod::Week, now), 1705276800); // 2024-01-15
+    assert_eq!(period_start(TimePeriod::Month, now), 1704067200); // 2024-01-01
+}
+
+#[tokio::test]
+async fn test_board_keys() -> Result<()> {
+    let (app, database) = test_app_with_database(test_config()).await?;
+    let mut app = app.into_service();
+
+    let keys = create_board(&mut app, "test-table").await?;
+    let player = create_player(&mut app, "nertsal").await?;
+
+    // Only admin can manage keys
+    let response = send(
+        &mut app,
+        Request::get("/board/test-table/keys")
+            .header("api-key", &keys.submit)
+            .body(Body::empty())?,
+    )
+    .await?;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = send(
+        &mut app,
+        Request::get("/board/test-table/keys")
+            .header("api-key", &keys.admin)
+            .body(Body::empty())?,
+    )
+    .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    let infos: Vec<nertboard_core::KeyInfo> = response_json(response).await?;
+    let labels: Vec<&str> = infos.iter().map(|info| info.label.as_str()).collect();
+    assert_eq!(labels, vec!["read", "submit", "admin"]);
+    let submit_id = infos[1].id;
+    let admin_id = infos[2].id;
+    assert!(infos[2].last_used_at.is_some());
+
+    // Mint a labelled key
+    let response = send(
+        &mut app,
+        request_json(
+            Request::post("/board/test-table/keys").header("api-key", &keys.admin),
+            &nertboard_core::KeyCreate {
+                label: "demo build".to_string(),
+                authority: KeyAuthority::Submit,
+                expires_at: None,
+            },
+        )?,
+    )
+    .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    let demo: nertboard_core::NewKey = response_json(response).await?;
+    let response = submit_score(&mut app, "test-table", &demo.key, &player, 10).await?;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Keys need a label and cannot be created already expired
+    let now = Utc::now().timestamp();
+    for (label, expires_at) in [(" ", None), ("expired", Some(now - 1))] {
+        let response = send(
+            &mut app,
+            request_json(
+                Request::post("/board/test-table/keys").header("api-key", &keys.admin),
+                &nertboard_core::KeyCreate {
+                    label: label.to_string(),
+                    authority: KeyAuthority::Submit,
+                    expires_at,
+                },
+            )?,
+        )
+        .await?;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let error: ErrorResponse = response_json(response).await?;
+        assert_eq!(error.code, ErrorCode::InvalidSettings);
+    }
+
+    // Expired keys are rejected
+    let mut create_expired = async |authority: KeyAuthority| -> Result<nertboard_core::NewKey> {
+        let response = send(
+            &mut app,
+            request_json(
+                Request::post("/board/test-table/keys").header("api-key", &keys.admin),
+                &nertboard_core::KeyCreate {
+                    label: "expired".to_string(),
+                    authority,
+                    expires_at: Some(now + 60),
+                },
+            )?,
+        )
+        .await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        let key: nertboard_core::NewKey = response_json(response).await?;
+        sqlx::query("UPDATE board_keys SET expires_at = ? WHERE key_id = ?")
+            .bind(now - 1)
+            .bind(key.info.id)
+            .execute(&*database)
+            .await?;
+        Ok(key)
+    };
+    let expired = create_expired(KeyAuthority::Submit).await?;
+    let expired_admin = create_expired(KeyAuthority::Admin).await?;
+    let response = submit_score(&mut app, "test-table", &expired.key, &player, 10).await?;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // Revoke
+    let response = send(
+        &mut app,
+        Request::delete(format!("/board/test-table/keys/{}", demo.info.id))
+            .header("api-key", &keys.admin)
+            .body(Body::empty())?,
+    )
+    .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = submit_score(&mut app, "test-table", &demo.key, &player, 10).await?;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // The only working admin key cannot be revoked, expired ones do not count
+    let response = send(
+        &mut app,
+        Request::delete(format!("/board/test-table/keys/{}", admin_id))
+            .header("api-key", &keys.admin)
+            .body(Body::empty())?,
+    )
+    .await?;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    let response = send(
+        &mut app,
+        Request::delete(format!("/board/test-table/keys/{}", expired_admin.info.id))
+            .header("api-key", &keys.admin)
+            .body(Body::empty())?,
+    )
+    .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Rotate
+    let response = send(
+        &mut app,
+        Request::post(format!("/board/test-table/keys/{}/rotate", submit_id))
+            .header("api-key", &keys.admin)
+            .body(Body::empty())?,
+    )
+    .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    let rotated: nertboard_core::NewKey = response_json(response).await?;
+    assert_eq!(rotated.info.authority, KeyAuthority::Submit);
+
+    let response = submit_score(&mut app, "test-table", &keys.submit, &player, 10).await?;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = submit_score(&mut app, "test-table", &rotated.key, &player, 10).await?;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_concurrent_key_revocation() -> Result<()> {
+    let (database, _file) = test_file_database().await?;
+    let app = app(AppState::new(Arc::new(database), test_config())?);
+    let mut service = app.clone().into_service();
+
+    let keys = create_board(&mut service, "test-table").await?;
+    let mut admins = vec![keys.admin.clone()];
+    for _ in 0..4 {
+        let response = send(
+            &mut service,
+            request_json(
+                Request::post("/board/test-table/keys").header("api-key", &keys.admin),
+                &nertboard_core::KeyCreate {
+                    label: "admin".to_string(),
+                    authority: KeyAuthority::Admin,
+                    expires_at: None,
+                },
+            )?,
+        )
+        .await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        let key: nertboard_core::NewKey = response_json(response).await?;
+        admins.push(key.key);
+    }
+    let response = send(
+        &mut service,
+        Request::get("/board/test-table/keys")
+            .header("api-key", &keys.admin)
+            .body(Body::empty())?,
+    )
+    .await?;
+    let infos: Vec<nertboard_core::KeyInfo> = response_json(response).await?;
+    let admin_ids = infos
+        .iter()
+        .filter(|info| info.authority == KeyAuthority::Admin)
+        .map(|info| info.id);
+
+    // Every admin key revokes itself in parallel, one of them has to stay
+    let revocations = admins.into_iter().zip(admin_ids).map(|(key, id)| {
+        let mut service = app.clone().into_service();
+        tokio::spawn(async move {
+            let request = Request::delete(format!("/board/test-table/keys/{}", id))
+                .header("api-key", key)
+                .body(Body::empty())?;
+            let response = send(&mut service, request).await?;
+            Ok::<_, color_eyre::Report>(response.status())
+        })
+    });
+    let mut revoked = 0;
+    for revocation in revocations.collect::<Vec<_>>() {
+        match revocation.await?? {
+            StatusCode::OK => revoked += 1,
+            status => assert_eq!(status, StatusCode::CONFLICT),
+        }
+    }
+    assert_eq!(revoked, 4);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_public_read() -> Result<()> {
+    let mut app = test_app().await?.into_service();
+
+    let settings = BoardSettings {
+        public_read: true,
+        ..Default::default()
+    };
+    let keys = create_board_with(&mut app, "test-table", settings).await?;
+    let player = create_player(&mut app, "nertsal").await?;
+    let response = submit_score(&mut app, "test-table", &keys.submit, &player, 10).await?;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Read without a key
+    let response = send(
+        &mut app,
+        Request::get("/board/test-table").body(Body::empty())?,
+    )
+    .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = send(
+        &mut app,
+        Request::get(format!("/board/test-table/player/{}", player.id)).body(Body::empty())?,
+    )
+    .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Submitting still requires a key
+    let response = submit_score(&mut app, "test-table", "", &player, 10).await?;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // Make private
+    let response = send(
+        &mut app,
+        request_json(
+            Request::patch("/board/test-table/settings").header("api-key", &keys.admin),
+            &nertboard_core::BoardSettingsUpdate {
+                public_read: Some(false),
+                ..Default::default()
+            },
+        )?,
+    )
+    .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    let settings: BoardSettings = response_json(response).await?;
+    assert!(!settings.public_read);
+
+    let response = send(
+        &mut app,
+        Request::get("/board/test-table").body(Body::empty())?,
+    )
+    .await?;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_player_recovery() -> Result<()> {
+    let (app, database) = test_app_with_database(test_config()).await?;
+    let mut app = app.into_service();
+
+    let keys = create_board(&mut app, "test-table").await?;
+    let player = create_player(&mut app, "nertsal").await?;
+    let recovery_code = player.recovery_code.clone().expect("no recovery code");
+
+    // Regenerate the key
+    let response = send(
+        &mut app,
+        Request::post(format!("/player/{}/regenerate", player.id))
+            .header("player-key", &player.key)
+            .body(Body::empty())?,
+    )
+    .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    let regenerated: Player = response_json(response).await?;
+    assert_ne!(regenerated.key, player.key);
+    assert!(regenerated.recovery_code.is_none());
+
+    let response = submit_score(&mut app, "test-table", &keys.submit, &player, 10).await?;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = submit_score(&mut app, "test-table", &keys.submit, &regenerated, 10).await?;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Unknown player
+    let response = send(
+        &mut app,
+        Request::post("/player/100/regenerate")
+            .header("player-key", &player.key)
+            .body(Body::empty())?,
+    )
+    .await?;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    // Recover with an invalid code
+    let recover = format!("/player/{}/recover", player.id);
+    let response = send(&mut app, request_json(Request::post(&recover), &"invalid")?).await?;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    // Recover
+    let response = send(
+        &mut app,
+        request_json(Request::post(&recover), &recovery_code)?,
+    )
+    .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    let recovered: Player = response_json(response).await?;
+    assert_eq!(recovered.name, "nertsal");
+    assert_ne!(recovered.recovery_code.as_ref(), Some(&recovery_code));
+
+    let response = submit_score(&mut app, "test-table", &keys.submit, &regenerated, 10).await?;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = submit_score(&mut app, "test-table", &keys.submit, &recovered, 10).await?;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Recovery codes can only be used once
+    let response = send(
+        &mut app,
+        request_json(Request::post(&recover), &recovery_code)?,
+    )
+    .await?;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    // Players created before recovery codes have none
+    sqlx::query("UPDATE players SET recovery_hash = NULL WHERE player_id = ?")
+        .bind(player.id)
+        .execute(&*database)
+        .await?;
+    let response = send(&mut app, request_json(Request::post(&recover), &"invalid")?).await?;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_player_names() -> Result<()> {
+    let config = Config {
+        names: NameRules {
+            max_length: 10,
+            unique: true,
+            ..Default::default()
+        },
+        ..test_config()
+    };
+    let mut app = test_app_with(config).await?.into_service();
+
+    let mut create = async |name: &str| {
+        send(
+            &mut app,
+            request_json(Request::post("/player/create"), &name)?,
+        )
+        .await
+    };
+
+    for name in ["", "   ", "nertsal nertsal", "nert$al", "nert\nsal"] {
+        let response = create(name).await?;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let error: ErrorResponse = response_json(response).await?;
+        assert_eq!(error.code, ErrorCode::InvalidPlayerName);
+    }
+
+    // Names are trimmed
+    let response = create("  nertsal ").await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    let player: Player = response_json(response).await?;
+    assert_eq!(player.name, "nertsal");
+
+    // Names are unique ignoring case
+    let response = create("Nertsal").await?;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // Renaming follows the same rules
+    let other = create_player(&mut app, "other").await?;
+    for name in ["NERTSAL", "nert$al"] {
+        let response = rename_player(&mut app, &other, name).await?;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+    let response = rename_player(&mut app, &other, "Other").await?;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_player_rename() -> Result<()> {
+    let mut app = test_app().await?.into_service();
+
+    let keys = create_board(&mut app, "test-table").await?;
+    let player = create_player(&mut app, "nertsal").await?;
+
+    // Submitting does not rename the player
+    let typo = Player {
+        name: "nertsa".to_owned(),
+        ..player.clone()
+    };
+    let response = submit_score(&mut app, "test-table", &keys.submit, &typo, 10).await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    let scores = fetch_scores(&mut app, "test-table", &keys.read, "").await?;
+    assert_eq!(scores.scores[0].player, "nertsal");
+
+    // Rename
+    let response = rename_player(&mut app, &player, " nertboard ").await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    let name: String = response_json(response).await?;
+    assert_eq!(name, "nertboard");
+    let scores = fetch_scores(&mut app, "test-table", &keys.read, "").await?;
+    assert_eq!(scores.scores[0].player, "nertboard");
+
+    // Renaming requires the player key
+    let impostor = Player {
+        key: "invalid".to_owned(),
+        ..player.clone()
+    };
+    let response = rename_player(&mut app, &impostor, "impostor").await?;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    // History
+    let response = send(
+        &mut app,
+        Request::get(format!("/player/{}/names", player.id))
+            .header("player-key", &player.key)
+            .body(Body::empty())?,
+    )
+    .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    let history: Vec<nertboard_core::NameChange> = response_json(response).await?;
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].old_name, "nertsal");
+    assert_eq!(history[0].new_name, "nertboard");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_content_filter() -> Result<()> {
+    let wordlist = std::env::temp_dir().join(format!("nertboard-wordlist-{}", std::process::id()));
+    std::fs::write(&wordlist, "# Blocked words\n\nheck\nDARN\n")?;
+    let filtered_app = async |mode: FilterMode| {
+        let config = Config {
+            filter: FilterConfig {
+                wordlist: Some(wordlist.clone()),
+                mode,
+            },
+            names: NameRules {
+                unique: true,
+                ..Default::default()
+            },
+            ..test_config()
+        };
+        Ok::<_, color_eyre::Report>(test_app_with(config).await?.into_service())
+    };
+    let extra_info = |info: &str| nertboard_core::ScoreEntry {
+        player: String::new(),
+        score: 10,
+        extra_info: Some(info.to_owned()),
+        submitted_at: None,
+    };
+
+    // Reject
+    let mut app = filtered_app(FilterMode::Reject).await?;
+    let response = send(
+        &mut app,
+        request_json(Request::post("/player/create"), &"Heckler")?,
+    )
+    .await?;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let error: ErrorResponse = response_json(response).await?;
+    assert_eq!(error.code, ErrorCode::RejectedText);
+
+    let keys = create_board(&mut app, "test-table").await?;
+    let player = create_player(&mut app, "nertsal").await?;
+    let response = rename_player(&mut app, &player, "darn").await?;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = send(
+        &mut app,
+        request_json(
+            Request::post(format!("/board/test-table?player_id={}", player.id))
+                .header("api-key", &keys.submit)
+                .header("player-key", &player.key),
+            &extra_info("oh darn"),
+        )?,
+    )
+    .await?;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // Mask
+    let mut app = filtered_app(FilterMode::Mask).await?;
+    let keys = create_board(&mut app, "test-table").await?;
+    let player = create_player(&mut app, "Heckler").await?;
+    assert_eq!(player.name, "****ler");
+    // Uniqueness applies to the stored names
+    let response = send(
+        &mut app,
+        request_json(Request::post("/player/create"), &"Darnler")?,
+    )
+    .await?;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = send(
+        &mut app,
+        request_json(
+            Request::post(format!("/board/test-table?player_id={}", player.id))
+                .header("api-key", &keys.submit)
+                .header("player-key", &player.key),
+            &extra_info("oh darn"),
+        )?,
+    )
+    .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    let scores = fetch_scores(&mut app, "test-table", &keys.read, "").await?;
+    assert_eq!(scores.scores[0].extra_info.as_deref(), Some("oh ****"));
+
+    // Flag
+    let mut app = filtered_app(FilterMode::Flag).await?;
+    let keys = create_board(&mut app, "test-table").await?;
+    let player = create_player(&mut app, "Heckler").await?;
+    assert_eq!(player.name, "Heckler");
+    for info in ["oh darn", "nice"] {
+        let response = send(
+            &mut app,
+            request_json(
+                Request::post(format!("/board/test-table?player_id={}", player.id))
+                    .header("api-key", &keys.submit)
+                    .header("player-key", &player.key),
+                &extra_info(info),
+            )?,
+        )
+        .await?;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    let response = send(
+        &mut app,
+        Request::get("/board/test-table/scores?flagged=true")
+            .header("api-key", &keys.admin)
+            .body(Body::empty())?,
+    )
+    .await?;
+    let records: Vec<nertboard_core::ScoreRecord> = response_json(response).await?;
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].extra_info.as_deref(), Some("oh darn"));
+
+    std::fs::remove_file(&wordlist)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_moderation() -> Result<()> {
+    let mut app = test_app().await?.into_service();
+
+    let keys = create_board(&mut app, "test-table").await?;
+    let cheater = create_player(&mut app, "cheater").await?;
+    let player = create_player(&mut app, "nertsal").await?;
+    for (player, score) in [(&cheater, 1000), (&cheater, 999), (&player, 10)] {
+        let response = submit_score(&mut app, "test-table", &keys.submit, player, score).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    let mut list_records = async |query: &str| {
+        let response = send(
+            &mut app,
+            Request::get(format!("/board/test-table/scores{}", query))
+                .header("api-key", &keys.admin)
+                .body(Body::empty())?,
+        )
+        .await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        response_json::<Vec<nertboard_core::ScoreRecord>>(response).await
+    };
+    let records = list_records("").await?;
+    assert_eq!(records.len(), 3);
+    let records = list_records(&format!("?player_id={}", cheater.id)).await?;
+    assert_eq!(
+        records
+            .iter()
+            .map(|record| record.score)
+            .collect::<Vec<_>>(),
+        vec![1000, 999]
+    );
+    let cheated_id = records[0].id;
+
+    // Requires the admin key
+    let response = send(
+        &mut app,
+        Request::get("/board/test-table/scores")
+            .header("api-key", &keys.submit)
+            .body(Body::empty())?,
+    )
+    .await?;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    // Hide a score
+    let response = send(
+        &mut app,
+        request_json(
+            Request::patch(format!("/board/test-table/scores/{}", cheated_id))
+                .header("api-key", &keys.admin),
+            &nertboard_core::ScoreUpdate {
+                hidden: Some(true),
+                flagged: None,
+            },
+        )?,
+    )
+    .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    let record: nertboard_core::ScoreRecord = response_json(response).await?;
+    assert!(record.hidden);
+    let page = fetch_scores(&mut app, "test-table", &keys.read, "").await?;
+    assert_eq!(page.total, 2);
+    assert_eq!(page.scores[0].score, 999);
+
+    // Delete a score
+    let delete_score = |score_id: Id| {
+        Request::delete(format!("/board/test-table/scores/{}", score_id))
+            .header("api-key", &keys.admin)
+            .body(Body::empty())
+    };
+    let response = send(&mut app, delete_score(cheated_id)?).await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = send(&mut app, delete_score(cheated_id)?).await?;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let error: ErrorResponse = response_json(response).await?;
+    assert_eq!(error.code, ErrorCode::NoSuchScore);
+
+    // Wipe the scores of a player
+    let response = send(
+        &mut app,
+        Request::delete(format!("/board/test-table/player/{}/scores", cheater.id))
+            .header("api-key", &keys.admin)
+            .body(Body::empty())?,
+    )
+    .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    let page = fetch_scores(&mut app, "test-table", &keys.read, "").await?;
+    assert_eq!(page.total, 1);
+    assert_eq!(page.scores[0].player, "nertsal");
+
+    // Ban, banning again changes nothing
+    let ban = format!("/board/test-table/bans/{}", cheater.id);
+    for _ in 0..2 {
+        let response = send(
+            &mut app,
+            Request::put(&ban)
+                .header("api-key", &keys.admin)
+                .body(Body::empty())?,
+        )
+        .await?;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    let response = submit_score(&mut app, "test-table", &keys.submit, &cheater, 1000).await?;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let error: ErrorResponse = response_json(response).await?;
+    assert_eq!(error.code, ErrorCode::PlayerBanned);
+
+    let response = send(
+        &mut app,
+        Request::get("/board/test-table/bans")
+            .header("api-key", &keys.admin)
+            .body(Body::empty())?,
+    )
+    .await?;
+    let bans: Vec<nertboard_core::BanInfo> = response_json(response).await?;
+    assert_eq!(bans.len(), 1);
+    assert_eq!(bans[0].player_id, cheater.id);
+
+    // Unban
+    let response = send(
+        &mut app,
+        Request::delete(&ban)
+            .header("api-key", &keys.admin)
+            .body(Body::empty())?,
+    )
+    .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = submit_score(&mut app, "test-table", &keys.submit, &cheater, 5).await?;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // The player is not banned anymore
+    let response = send(
+        &mut app,
+        Request::delete(&ban)
+            .header("api-key", &keys.admin)
+            .body(Body::empty())?,
+    )
+    .await?;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_score_validation() -> Result<()> {
+    let (app, database) = test_app_with_database(test_config()).await?;
+    let mut app = app.into_service();
+
+    // Inconsistent rules
+    let settings = BoardSettings {
+        validation: ScoreValidation {
+            min_score: Some(10),
+            max_score: Some(0),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let board = BoardCreate {
+        name: "test-table".to_string(),
+        settings,
+    };
+    let response = send(
+        &mut app,
+        request_json(Request::post("/board/create"), &board)?,
+    )
+    .await?;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let error: ErrorResponse = response_json(response).await?;
+    assert_eq!(error.code, ErrorCode::InvalidSettings);
+
+    let settings = BoardSettings {
+        validation: ScoreValidation {
+            min_score: Some(0),
+            max_score: Some(100),
+            submission_limit: Some(SubmissionLimit {
+                count: 3,
+                window: 60,
+            }),
+            max_info_length: Some(5),
+        },
+        ..Default::default()
+    };
+    let keys = create_board_with(&mut app, "test-table", settings.clone()).await?;
+    let player = create_player(&mut app, "nertsal").await?;
+
+    let mut submit = async |score: Score, extra_info: Option<&str>| {
+        let entry = nertboard_core::ScoreEntry {
+            player: player.name.clone(),
+            score,
+            extra_info: extra_info.map(str::to_owned),
+            submitted_at: None,
+        };
+        let response = send(
+            &mut app,
+            request_json(
+                Request::post(format!("/board/test-table?player_id={}", player.id))
+                    .header("api-key", &keys.submit)
+                    .header("player-key", &player.key),
+                &entry,
+            )?,
+        )
+        .await?;
+        Ok::<_, color_eyre::Report>(response.status())
+    };
+
+    assert_eq!(submit(-1, None).await?, StatusCode::BAD_REQUEST);
+    assert_eq!(submit(101, None).await?, StatusCode::BAD_REQUEST);
+    assert_eq!(submit(50, Some("too long")).await?, StatusCode::BAD_REQUEST);
+
+    // Rejected scores do not count towards the limit
+    assert_eq!(submit(0, Some("short")).await?, StatusCode::OK);
+    assert_eq!(submit(100, None).await?, StatusCode::OK);
+    assert_eq!(submit(50, None).await?, StatusCode::OK);
+    assert_eq!(submit(50, None).await?, StatusCode::BAD_REQUEST);
+
+    // Submissions out of the window are removed on the next submission
+    let count_submissions = async || {
+        sqlx::query("SELECT COUNT(*) AS submissions FROM submissions")
+            .try_map(|row: AnyRow| row.try_get::<i64, _>("submissions"))
+            .fetch_one(&*database)
+            .await
+    };
+    sqlx::query("UPDATE submissions SET submitted_at = submitted_at - 60")
+        .execute(&*database)
+        .await?;
+    assert_eq!(submit(50, None).await?, StatusCode::OK);
+    assert_eq!(count_submissions().await?, 1);
+
+    // Settings are returned with the rules
+    let response = send(
+        &mut app,
+        request_json(
+            Request::patch("/board/test-table/settings").header("api-key", &keys.admin),
+            &nertboard_core::BoardSettingsUpdate {
+                validation: Some(ScoreValidation::default()),
+                ..Default::default()
+            },
+        )?,
+    )
+    .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    let updated: BoardSettings = response_json(response).await?;
+    assert_eq!(updated.validation, ScoreValidation::default());
+
+    let response = submit_score(&mut app, "test-table", &keys.submit, &player, 1000).await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    // Without a limit submissions are not recorded
+    assert_eq!(count_submissions().await?, 1);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_concurrent_submissions() -> Result<()> {
+    let (database, _file) = test_file_database().await?;
+    let app = app(AppState::new(Arc::new(database), test_config())?);
+    let mut service = app.clone().into_service();
+
+    let settings = BoardSettings {
+        validation: ScoreValidation {
+            submission_limit: Some(SubmissionLimit {
+                count: 3,
+                window: 60,
+            }),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let keys = create_board_with(&mut service, "test-table", settings).await?;
+    let player = create_player(&mut service, "nertsal").await?;
+
+    // Parallel submissions cannot get past the limit
+    let submissions = (0..10).map(|score| {
+        let mut service = app.clone().into_service();
+        let keys = keys.clone();
+        let player = player.clone();
+        tokio::spawn(async move {
+            let response =
+                submit_score(&mut service, "test-table", &keys.submit, &player, score).await?;
+            Ok::<_, color_eyre::Report>(response.status())
+        })
+    });
+    let mut accepted = 0;
+    for submission in submissions.collect::<Vec<_>>() {
+        match submission.await?? {
+            StatusCode::OK => accepted += 1,
+            status => assert_eq!(status, StatusCode::BAD_REQUEST),
+        }
+    }
+    assert_eq!(accepted, 3);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_signed_submissions() -> Result<()> {
+    use nertboard_core::signing;
+
+    let mut app = test_app().await?.into_service();
+
+    let keys = create_board(&mut app, "test-table").await?;
+    let player = create_player(&mut app, "nertsal").await?;
+
+    let signing_request = |method: &str| {
+        Request::builder()
+            .method(method)
+            .uri("/board/test-table/signing")
+            .header("api-key", &keys.admin)
+            .body(Body::empty())
+    };
+    let response = send(&mut app, signing_request("POST")?).await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    let secret: nertboard_core::SigningSecret = response_json(response).await?;
+
+    let entry = nertboard_core::ScoreEntry {
+        player: player.name.clone(),
+        score: 10,
+        extra_info: Some("signed".to_string()),
+        submitted_at: None,
+    };
+    let mut submit = async |secret: &str, timestamp: i64, nonce: &str| {
+        let message =
+            signing::submission_message("test-table", player.id, &entry, timestamp, nonce);
+        let response = send(
+            &mut app,
+            request_json(
+                Request::post(format!("/board/test-table?player_id={}", player.id))
+                    .header("api-key", &keys.submit)
+                    .header("player-key", &player.key)
+                    .header(signing::SIGNATURE_HEADER, signing::sign(secret, &message))
+                    .header(signing::TIMESTAMP_HEADER, timestamp)
+                    .header(signing::NONCE_HEADER, nonce),
+                &entry,
+            )?,
+        )
+        .await?;
+        Ok::<_, color_eyre::Report>(response.status())
+    };
+
+    let now = Utc::now().timestamp();
+    assert_eq!(submit(&secret.secret, now, "a").await?, StatusCode::OK);
+    // Replay
+    assert_eq!(
+        submit(&secret.secret, now, "a").await?,
+        StatusCode::FORBIDDEN
+    );
+    // Wrong secret
+    assert_eq!(submit("secret", now, "b").await?, StatusCode::FORBIDDEN);
+    // Stale timestamp
+    assert_eq!(
+        submit(&secret.secret, now - 3600, "c").await?,
+        StatusCode::FORBIDDEN
+    );
+    assert_eq!(submit(&secret.secret, now, "d").await?, StatusCode::OK);
+
+    // Unsigned
+    let response = submit_score(&mut app, "test-table", &keys.submit, &player, 10).await?;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let error: ErrorResponse = response_json(response).await?;
+    assert_eq!(error.code, ErrorCode::InvalidSignature);
+
+    // Disable signing
+    let response = send(&mut app, signing_request("DELETE")?).await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = submit_score(&mut app, "test-table", &keys.submit, &player, 10).await?;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_rate_limits() -> Result<()> {
+    let mut config = Config {
+        rate_limits: RateLimits {
+            player: Some(2),
+            submit: Some(2),
+            read: Some(1),
+        },
+        ..test_config()
+    };
+    config.server.trusted_proxies = vec![[10, 0, 0, 9].into()];
+    let mut app = test_app_with(config).await?.into_service();
+
+    let from =
+        |mut request: Request<Body>, address: [u8; 4], forwarded: Option<&str>| {
+            request.extensions_mut().insert(axum::extract::ConnectInfo(
+                std::net::SocketAddr::from((address, 1234)),
+            ));
+            if let Some(forwarded) = forwarded {
+                request
+                    .headers_mut()
+                    .insert("x-forwarded-for", forwarded.parse().unwrap());
+            }
+            request
+        };
+
+    // Player creation is limited by address
+    let create = || request_json(Request::post("/player/create"), &"nertsal");
+    for _ in 0..2 {
+        let response = send(&mut app, from(create()?, [10, 0, 0, 1], None)).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    let response = send(&mut app, from(create()?, [10, 0, 0, 1], None)).await?;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    let retry_after: u64 = response.headers()["retry-after"].to_str()?.parse()?;
+    assert!(retry_after > 0 && retry_after <= 30);
+    let error: ErrorResponse = response_json(response).await?;
+    assert_eq!(error.code, ErrorCode::TooManyRequests);
+    let response = send(&mut app, from(create()?, [10, 0, 0, 2], None)).await?;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Only trusted proxies can tell the address of the client
+    let spoofed = Some("10.0.0.3");
+    let response = send(&mut app, from(create()?, [10, 0, 0, 1], spoofed)).await?;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    for _ in 0..2 {
+        let response = send(
+            &mut app,
+            from(create()?, [10, 0, 0, 9], Some("10.0.0.1, 10.0.0.3")),
+        )
+        .await?;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    let response = send(&mut app, from(create()?, [10, 0, 0, 9], Some("10.0.0.3"))).await?;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    let response = send(&mut app, from(create()?, [10, 0, 0, 9], Some("10.0.0.4"))).await?;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Submissions are limited by player and by api key once they are verified,
+    // so guessing requests cannot spend the budget of someone else
+    let keys = create_board(&mut app, "test-table").await?;
+    let player = create_player(&mut app, "nertsal").await?;
+    let other = create_player(&mut app, "other").await?;
+    let third = create_player(&mut app, "third").await?;
+    let impostor = Player {
+        key: "wrong-key".to_owned(),
+        ..player.clone()
+    };
+    for score in 0..3 {
+        let response = submit_score(&mut app, "test-table", &keys.submit, &impostor, score).await?;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+    let response = submit_score(&mut app, "test-table", &keys.submit, &player, 1).await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = submit_score(&mut app, "test-table", &keys.admin, &player, 2).await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = submit_score(&mut app, "test-table", &keys.admin, &player, 3).await?;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    let response = submit_score(&mut app, "test-table", &keys.submit, &other, 4).await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    // The submit key has been used twice
+    let response = submit_score(&mut app, "test-table", &keys.submit, &third, 5).await?;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    let response = submit_score(&mut app, "test-table", &keys.admin, &third, 5).await?;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Reads have a separate budget
+    let read = |key: &str| {
+        Request::get("/board/test-table")
+            .header("api-key", key)
+            .body(Body::empty())
+    };
+    let response = send(&mut app, from(read(&keys.read)?, [10, 0, 0, 1], None)).await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = send(&mut app, from(read(&keys.admin)?, [10, 0, 0, 1], None)).await?;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    let response = send(&mut app, from(read(&keys.read)?, [10, 0, 0, 2], None)).await?;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    let response = send(&mut app, from(read(&keys.admin)?, [10, 0, 0, 3], None)).await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    // Unknown keys are not tracked
+    let response = send(&mut app, from(read("wrong-key")?, [10, 0, 0, 4], None)).await?;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_concurrent_board_creation() -> Result<()> {
+    let config = Config {
+        board_creation: BoardCreation {
+            open: false,
+            operators: vec![Operator {
+                name: "ci".to_owned(),
+                key: "operator-key".to_owned(),
+                max_boards: Some(2),
+            }],
+        },
+        ..Default::default()
+    };
+    let (database, _file) = test_file_database().await?;
+    let app = app(AppState::new(Arc::new(database), config)?);
+
+    // Parallel requests cannot get past the quota
+    let creations = (0..10).map(|i| {
+        let mut service = app.clone().into_service();
+        tokio::spawn(async move {
+            let board = BoardCreate {
+                name: format!("test-table-{}", i),
+                settings: BoardSettings::default(),
+            };
+            let request = request_json(
+                Request::post("/board/create").header("operator-key", "operator-key"),
+                &board,
+            )?;
+            let response = send(&mut service, request).await?;
+            Ok::<_, color_eyre::Report>(response.status())
+        })
+    });
+    let mut created = 0;
+    for creation in creations.collect::<Vec<_>>() {
+        match creation.await?? {
+            StatusCode::OK => created += 1,
+            status => assert_eq!(status, StatusCode::FORBIDDEN),
+        }
+    }
+    assert_eq!(created, 2);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_board_creation() -> Result<()> {
+    let config = Config {
+        board_creation: BoardCreation {
+            open: false,
+            operators: vec![Operator {
+                name: "ci".to_owned(),
+                key: "operator-key".to_owned(),
+                max_boards: Some(1),
+            }],
+        },
+        ..Default::default()
+    };
+    let mut app = test_app_with(config).await?.into_service();
+
+    let create = |name: &str, operator_key: Option<&str>| {
+        let mut request = Request::post("/board/create");
+        if let Some(key) = operator_key {
+            request = request.header("operator-key", key);
+        }
+        let board = BoardCreate {
+            name: name.to_owned(),
+            settings: BoardSettings::default(),
+        };
+        request_json(request, &board)
+    };
+
+    // Missing or unknown key
+    let response = send(&mut app, create("test-table", None)?).await?;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = send(&mut app, create("test-table", Some("wrong-key"))?).await?;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = send(&mut app, create("test-table", Some("operator-key"))?).await?;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Over the quota
+    let response = send(&mut app, create("another-table", Some("operator-key"))?).await?;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let error: ErrorResponse = response_json(response).await?;
+    assert_eq!(error.code, ErrorCode::QuotaExceeded);
+
+    // Listing boards also requires the key
+    let list = |operator_key: Option<&str>| {
+        let mut request = Request::get("/boards");
+        if let Some(key) = operator_key {
+            request = request.header("operator-key", key);
+        }
+        request.body(Body::empty())
+    };
+    let response = send(&mut app, list(None)?).await?;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = send(&mut app, list(Some("operator-key"))?).await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    let boards: Vec<BoardInfo> = response_json(response).await?;
+    assert_eq!(boards.len(), 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_board_info() -> Result<()> {
+    let mut app = test_app().await?.into_service();
+
+    let keys = create_board(&mut app, "test-table").await?;
+    create_board(&mut app, "another-table").await?;
+    let player = create_player(&mut app, "nertsal").await?;
+    let other = create_player(&mut app, "other").await?;
+    for (player, score) in [(&player, 10), (&player, 20), (&other, 15)] {
+        let response = submit_score(&mut app, "test-table", &keys.submit, player, score).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    let info_request = |method: &str, key: &str| {
+        Request::builder()
+            .method(method)
+            .uri("/board/test-table/info")
+            .header("api-key", key)
+    };
+    let response = send(
+        &mut app,
+        info_request("GET", &keys.read).body(Body::empty())?,
+    )
+    .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    let info: BoardInfo = response_json(response).await?;
+    assert_eq!(info.name, "test-table");
+    assert_eq!(info.display_name, None);
+    assert!(info.created_at.is_some());
+    assert_eq!(info.score_count, 3);
+    assert_eq!(info.player_count, 2);
+    assert_eq!(info.settings, BoardSettings::default());
+
+    // Only admins can change the description
+    let update = BoardInfoUpdate {
+        display_name: Some(" Test Table ".to_owned()),
+        description: Some("Scores for testing".to_owned()),
+    };
+    let response = send(
+        &mut app,
+        request_json(info_request("PATCH", &keys.submit), &update)?,
+    )
+    .await?;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = send(
+        &mut app,
+        request_json(info_request("PATCH", &keys.admin), &update)?,
+    )
+    .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    let info: BoardInfo = response_json(response).await?;
+    assert_eq!(info.display_name.as_deref(), Some("Test Table"));
+    assert_eq!(info.description.as_deref(), Some("Scores for testing"));
+
+    // Empty text removes the value, missing fields are kept
+    let update = BoardInfoUpdate {
+        display_name: Some(String::new()),
+        description: None,
+    };
+    let response = send(
+        &mut app,
+        request_json(info_request("PATCH", &keys.admin), &update)?,
+    )
+    .await?;
+    let info: BoardInfo = response_json(response).await?;
+    assert_eq!(info.display_name, None);
+    assert_eq!(info.description.as_deref(), Some("Scores for testing"));
+
+    // Anyone can list boards in the open creation mode
+    let response = send(
+        &mut app,
+        Request::get("/boards?limit=1").body(Body::empty())?,
+    )
+    .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    let boards: Vec<BoardInfo> = response_json(response).await?;
+    assert_eq!(boards.len(), 1);
+    assert_eq!(boards[0].name, "another-table");
+
+    Ok(())
+}
@@ -0,0 +1,165 @@
+use super::*;
+
+use nertboard_core::{ScoreEntry, ScoreValidation, SubmissionLimit};
+
+/// Decodes the validation rules from a row of the `boards` table.
+pub(super) fn decode_validation(row: &AnyRow) -> sqlx::Result<ScoreValidation> {
+    let count = decode_optional::<i32>(row, "submission_count")?;
+    let window = decode_optional::<i32>(row, "submission_window")?;
+    Ok(ScoreValidation {
+        min_score: decode_optional(row, "min_score")?,
+        max_score: decode_optional(row, "max_score")?,
+        submission_limit: count.zip(window).map(|(count, window)| SubmissionLimit {
+            count: count as u32,
+            window: window as u32,
+        }),
+        max_info_length: decode_optional::<i32>(row, "max_info_length")?
+            .map(|length| length as u32),
+    })
+}
+
+/// Checks that the rules are consistent.
+pub(super) fn check_rules(rules: &ScoreValidation) -> Result<()> {
+    if let (Some(min), Some(max)) = (rules.min_score, rules.max_score) {
+        if min > max {
+            return Err(RequestError::InvalidSettings(
+                "min_score is greater than max_score",
+            ));
+        }
+    }
+    if let Some(limit) = rules.submission_limit {
+        if limit.window == 0 {
+            return Err(RequestError::InvalidSettings(
+                "submission window must not be empty",
+            ));
+        }
+        if i32::try_from(limit.count).is_err() || i32::try_from(limit.window).is_err() {
+            return Err(RequestError::InvalidSettings(
+                "submission limit is too large",
+            ));
+        }
+    }
+    if rules
+        .max_info_length
+        .is_some_and(|length| i32::try_from(length).is_err())
+    {
+        return Err(RequestError::InvalidSettings(
+            "max_info_length is too large",
+        ));
+    }
+    Ok(())
+}
+
+/// Stores the rules of the board.
+pub(super) async fn save_rules<'c>(
+    executor: impl sqlx::Executor<'c, Database = sqlx::Any>,
+    board_id: Id,
+    rules: &ScoreValidation,
+) -> Result<()> {
+    // Values are checked to fit by `check_rules`
+    let as_i32 = |value: u32| value as i32;
+    sqlx::query(
+        "
+UPDATE boards
+SET min_score = ?, max_score = ?, submission_count = ?, submission_window = ?, max_info_length = ?
+WHERE board_id = ?
+        ",
+    )
+    .bind(rules.min_score)
+    .bind(rules.max_score)
+    .bind(rules.submission_limit.map(|limit| as_i32(limit.count)))
+    .bind(rules.submission_limit.map(|limit| as_i32(limit.window)))
+    .bind(rules.max_info_length.map(as_i32))
+    .bind(board_id)
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+/// Checks the score against the rules of the board.
+/// The submission limit is checked by [`record_submission`].
+pub(super) fn validate_score(board: &Board, score: &ScoreEntry) -> Result<()> {
+    let rules = &board.settings.validation;
+
+    if let Some(min) = rules.min_score {
+        if score.score < min {
+            return Err(RequestError::ScoreRejected(format!(
+                "score must be at least {}",
+                min
+            )));
+        }
+    }
+    if let Some(max) = rules.max_score {
+        if score.score > max {
+            return Err(RequestError::ScoreRejected(format!(
+                "score must be at most {}",
+                max
+            )));
+        }
+    }
+
+    if let (Some(max), Some(info)) = (rules.max_info_length, &score.extra_info) {
+        if info.chars().count() > max as usize {
+            return Err(RequestError::ScoreRejected(format!(
+                "extra info must be at most {} characters long",
+                max
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks the submission limit of the board, if it has one, and records the submission.
+/// Submissions that fell out of the window are no longer needed and get removed.
+/// Has to run in the transaction that inserts the score.
+pub(super) async fn record_submission(
+    connection: &mut sqlx::AnyConnection,
+    board: &Board,
+    player_id: Id,
+    now: i64,
+) -> Result<()> {
+    let Some(limit) = board.settings.validation.submission_limit else {
+        return Ok(());
+    };
+
+    // Lock the player first, so that concurrent submissions of the player
+    // wait for each other and count the ones committed in the meantime
+    sqlx::query("UPDATE players SET flagged = flagged WHERE player_id = ?")
+        .bind(player_id)
+        .execute(&mut *connection)
+        .await?;
+
+    let since = now - i64::from(limit.window);
+    sqlx::query("DELETE FROM submissions WHERE board_id = ? AND submitted_at <= ?")
+        .bind(board.id)
+        .bind(since)
+        .execute(&mut *connection)
+        .await?;
+    let submissions = sqlx::query(
+        "
+SELECT COUNT(*) AS submissions FROM submissions
+WHERE board_id = ? AND player_id = ? AND submitted_at > ?
+        ",
+    )
+    .bind(board.id)
+    .bind(player_id)
+    .bind(since)
+    .try_map(|row: AnyRow| row.try_get::<i64, _>("submissions"))
+    .fetch_one(&mut *connection)
+    .await?;
+    if submissions >= i64::from(limit.count) {
+        return Err(RequestError::ScoreRejected(format!(
+            "at most {} submissions are allowed every {} seconds",
+            limit.count, limit.window
+        )));
+    }
+
+    sqlx::query("INSERT INTO submissions (board_id, player_id, submitted_at) VALUES (?, ?, ?)")
+        .bind(board.id)
+        .bind(player_id)
+        .bind(now)
+        .execute(&mut *connection)
+        .await?;
+    Ok(())
+}
@@ -1,38 +1,67 @@
-use crate::{database::DatabasePool, prelude::*};
+use crate::{config::LogFormat, database::DatabasePool, prelude::*};
 
 use tracing::level_filters::LevelFilter;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
-
-pub fn setup() -> Result<()> {
-    // Panic handler
-    color_eyre::install()?;
+use tracing_subscriber::{
+    fmt::writer::BoxMakeWriter, layer::SubscriberExt, util::SubscriberInitExt, Layer,
+};
 
+/// Sets up logging and database drivers.
+/// Logs are written to stderr instead of stdout if `log_to_stderr` is set,
+/// so that they do not mix with the output of admin commands.
+pub fn setup(log_format: LogFormat, log_to_stderr: bool) -> Result<()> {
     // Setup logging
+    let writer = if log_to_stderr {
+        BoxMakeWriter::new(std::io::stderr)
+    } else {
+        BoxMakeWriter::new(std::io::stdout)
+    };
+    let output = tracing_subscriber::fmt::layer().with_writer(writer);
+    let output = match log_format {
+        LogFormat::Pretty => output.pretty().boxed(),
+        LogFormat::Compact => output.compact().boxed(),
+        LogFormat::Json => output.json().boxed(),
+    };
     tracing_subscriber::registry()
+        .with(output)
         .with(
             tracing_subscriber::EnvFilter::builder()
                 .with_default_directive(LevelFilter::INFO.into())
                 .from_env_lossy(),
         )
-        .with(
-            tracing_subscriber::fmt::layer()
-                .pretty()
-                .with_writer(std::io::stdout),
-        )
         .init();
 
-    // Load .env
-    dotenv::dotenv().ok(); // Error if file does not exist: ignore
-
     // SQL drivers
     sqlx::any::install_default_drivers();
 
     Ok(())
 }
 
-pub async fn connect_database(url: &str) -> Result<DatabasePool> {
+/// Sets up the environment once for all tests.
+#[cfg(test)]
+pub fn setup_test() {
+    static SETUP: std::sync::Once = std::sync::Once::new();
+    SETUP.call_once(|| {
+        setup(LogFormat::default(), false).expect("failed to set up the environment")
+    });
+}
+
+pub async fn connect_database(url: &str, pool_size: u32) -> Result<DatabasePool> {
     tracing::info!("Connecting to database {}", url);
-    let pool = DatabasePool::connect(url).await?;
+    let pool = sqlx::any::AnyPoolOptions::new()
+        .max_connections(pool_size)
+        .connect(url)
+        .await?;
+
+    let version = crate::database::check_schema_version(&pool)
+        .await
+        .context("when checking the schema version")?;
+    if version < crate::database::SCHEMA_VERSION {
+        tracing::info!(
+            "Database schema version {}, migrating to {}",
+            version,
+            crate::database::SCHEMA_VERSION
+        );
+    }
 
     crate::database::init_database(&pool)
         .await